@@ -0,0 +1,273 @@
+use serde_json::{Map, Value};
+
+use super::error::{ApplyError, Error, Result};
+
+/// Applies an RFC 6902 JSON Patch. Either every operation succeeds or the document is
+/// left untouched and the first failing operation is reported.
+pub fn apply_patch(document: &Value, patch: &Value) -> Result<Value> {
+    let operations = patch
+        .as_array()
+        .ok_or_else(|| apply_error(0, "", "patch is not an array"))?;
+    let mut patched = document.clone();
+    for (index, operation) in operations.iter().enumerate() {
+        apply_operation(&mut patched, operation)
+            .map_err(|(pointer, reason)| apply_error(index, &pointer, &reason))?;
+    }
+
+    Ok(patched)
+}
+
+/// Applies an RFC 7386 JSON Merge Patch.
+pub fn apply_merge_patch(document: &Value, patch: &Value) -> Value {
+    match patch {
+        Value::Object(patch_members) => {
+            let mut target = match document {
+                Value::Object(o) => o.clone(),
+                _ => Map::new(),
+            };
+            for (k, v) in patch_members {
+                if v.is_null() {
+                    target.shift_remove(k);
+                } else {
+                    let merged = apply_merge_patch(target.get(k).unwrap_or(&Value::Null), v);
+                    target.insert(k.to_string(), merged);
+                }
+            }
+            Value::Object(target)
+        }
+        _ => patch.clone(),
+    }
+}
+
+fn apply_error(op_index: usize, pointer: &str, reason: &str) -> Error {
+    Error::Apply(ApplyError {
+        op_index,
+        pointer: pointer.to_string(),
+        reason: reason.to_string(),
+    })
+}
+
+type OperationResult<T> = std::result::Result<T, (String, String)>;
+
+fn apply_operation(document: &mut Value, operation: &Value) -> OperationResult<()> {
+    let member = |name: &str| -> OperationResult<&Value> {
+        operation
+            .get(name)
+            .ok_or_else(|| (String::new(), format!("missing `{}` member", name)))
+    };
+    let pointer_member = |name: &str| -> OperationResult<String> {
+        member(name)?
+            .as_str()
+            .map(|x| x.to_string())
+            .ok_or_else(|| (String::new(), format!("`{}` is not a string", name)))
+    };
+    let op = pointer_member("op")?;
+    let path = pointer_member("path")?;
+    let with_path = |reason: String| (path.to_string(), reason);
+    match op.as_str() {
+        "add" => add(document, &path, member("value")?.clone()).map_err(with_path),
+        "remove" => remove(document, &path).map(|_| ()).map_err(with_path),
+        "replace" => {
+            let target = get_mut(document, &path).map_err(with_path)?;
+            *target = member("value")?.clone();
+            Ok(())
+        }
+        "move" => {
+            let from = pointer_member("from")?;
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(with_path("cannot move a value into itself".to_string()));
+            }
+            let value = remove(document, &from).map_err(|reason| (from.to_string(), reason))?;
+            add(document, &path, value).map_err(with_path)
+        }
+        "copy" => {
+            let from = pointer_member("from")?;
+            let value = get_mut(document, &from)
+                .map_err(|reason| (from.to_string(), reason))?
+                .clone();
+            add(document, &path, value).map_err(with_path)
+        }
+        "test" => {
+            let expected = member("value")?;
+            if get_mut(document, &path).map_err(with_path)? == expected {
+                Ok(())
+            } else {
+                Err(with_path(format!("value is not {}", expected)))
+            }
+        }
+        _ => Err(with_path(format!("unknown operation `{}`", op))),
+    }
+}
+
+fn parse_pointer(pointer: &str) -> std::result::Result<Vec<String>, String> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !pointer.starts_with('/') {
+        return Err("pointer does not start with `/`".to_string());
+    }
+    Ok(pointer[1..]
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+fn parse_index(token: &str, len: usize) -> std::result::Result<usize, String> {
+    let is_number = !token.is_empty()
+        && token.chars().all(|c| c.is_ascii_digit())
+        && (token == "0" || !token.starts_with('0'));
+    match token.parse::<usize>() {
+        Ok(index) if is_number && index < len => Ok(index),
+        _ => Err(format!("index `{}` is out of bounds", token)),
+    }
+}
+
+fn get_mut<'a>(
+    document: &'a mut Value,
+    pointer: &str,
+) -> std::result::Result<&'a mut Value, String> {
+    let mut current = document;
+    for token in parse_pointer(pointer)? {
+        current = match current {
+            Value::Object(o) => o
+                .get_mut(&token)
+                .ok_or_else(|| format!("key `{}` does not exist", token))?,
+            Value::Array(a) => {
+                let index = parse_index(&token, a.len())?;
+                &mut a[index]
+            }
+            _ => return Err(format!("cannot index a scalar with `{}`", token)),
+        };
+    }
+
+    Ok(current)
+}
+
+/// Splits a pointer into the pointer to its parent and the last token.
+fn split_last(pointer: &str) -> std::result::Result<(String, String), String> {
+    let mut tokens = parse_pointer(pointer)?;
+    let last = tokens
+        .pop()
+        .ok_or_else(|| "the root has no parent".to_string())?;
+    let parent: String = tokens
+        .iter()
+        .map(|x| format!("/{}", x.replace('~', "~0").replace('/', "~1")))
+        .collect();
+
+    Ok((parent, last))
+}
+
+fn add(document: &mut Value, pointer: &str, value: Value) -> std::result::Result<(), String> {
+    if pointer.is_empty() {
+        *document = value;
+        return Ok(());
+    }
+    let (parent, last) = split_last(pointer)?;
+    match get_mut(document, &parent)? {
+        Value::Object(o) => {
+            o.insert(last, value);
+        }
+        Value::Array(a) if last == "-" => a.push(value),
+        Value::Array(a) => {
+            let index = parse_index(&last, a.len() + 1)?;
+            a.insert(index, value);
+        }
+        _ => return Err("cannot add to a scalar".to_string()),
+    }
+
+    Ok(())
+}
+
+fn remove(document: &mut Value, pointer: &str) -> std::result::Result<Value, String> {
+    let (parent, last) = split_last(pointer)?;
+    match get_mut(document, &parent)? {
+        Value::Object(o) => o
+            .shift_remove(&last)
+            .ok_or_else(|| format!("key `{}` does not exist", last)),
+        Value::Array(a) => {
+            let index = parse_index(&last, a.len())?;
+            Ok(a.remove(index))
+        }
+        _ => Err("cannot remove from a scalar".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::json_diff::{diff, diff_with_options, DiffOptions};
+    use crate::patch::{json_patch, merge_patch};
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trip() {
+        let r =
+            r#"{"a": 1, "list": [1, 2, 3, {"x": "y"}], "gone": 0, "t": "s", "m": {"a~b": [1]}}"#;
+        let r2 =
+            r#"{"a": 2, "list": [0, 1, 3, {"x": "z"}, 5], "new": [0], "t": {}, "m": {"a~b": []}}"#;
+        let (a, b): (Value, Value) = (
+            serde_json::from_str(r).unwrap(),
+            serde_json::from_str(r2).unwrap(),
+        );
+        let json = diff(r, r2).unwrap();
+
+        assert_eq!(apply_patch(&a, &json_patch(&json)).unwrap(), b);
+        assert_eq!(apply_merge_patch(&a, &merge_patch(&json).unwrap()), b);
+    }
+
+    #[test]
+    fn test_round_trip_moves() {
+        let options = DiffOptions {
+            move_threshold: Some(0.5),
+            ..Default::default()
+        };
+        let r = r#"[1, 2, {"x": [3, 4, 5]}, 6, 7, 8]"#;
+        let r2 = r#"[8, 2, 9, 6, 1, 7, {"x": [4, 5, 3]}]"#;
+        let (a, b): (Value, Value) = (
+            serde_json::from_str(r).unwrap(),
+            serde_json::from_str(r2).unwrap(),
+        );
+        let patch = json_patch(&diff_with_options(r, r2, &options).unwrap());
+
+        assert!(patch.as_array().unwrap().iter().any(|x| x["op"] == "move"));
+        assert_eq!(apply_patch(&a, &patch).unwrap(), b);
+    }
+
+    #[test]
+    fn test_round_trip_renames() {
+        let options = DiffOptions {
+            rename_threshold: Some(0.5),
+            ..Default::default()
+        };
+        let r = r#"{"a": {"x": 1, "y": 2}, "b": "s", "c": 0}"#;
+        let r2 = r#"{"d": {"x": 1, "y": 3}, "e": "s", "c": 0}"#;
+        let (a, b): (Value, Value) = (
+            serde_json::from_str(r).unwrap(),
+            serde_json::from_str(r2).unwrap(),
+        );
+        let json = diff_with_options(r, r2, &options).unwrap();
+
+        assert_eq!(apply_patch(&a, &json_patch(&json)).unwrap(), b);
+        assert_eq!(apply_merge_patch(&a, &merge_patch(&json).unwrap()), b);
+    }
+
+    #[test]
+    fn test_failing_operation() {
+        let document = json!({"a": [1, 2]});
+        let patch = json!([
+            {"op": "remove", "path": "/a/0"},
+            {"op": "move", "from": "/a/0", "path": "/b"},
+            {"op": "test", "path": "/a/5", "value": 1},
+        ]);
+
+        assert_eq!(
+            apply_patch(&document, &patch),
+            Err(Error::Apply(ApplyError {
+                op_index: 2,
+                pointer: "/a/5".to_string(),
+                reason: "index `5` is out of bounds".to_string(),
+            }))
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use serde_json::Value;
+
+use super::json_diff::{array_entries, ArrayDiff, ArrayEntry, JsonV, ObjectDiff};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+    TypeChanged,
+    /// The keys of an object appear in a different order
+    Reordered,
+    /// An array element moved to another index
+    Moved,
+    /// An object key renamed to another key
+    Renamed,
+}
+
+/// A single difference between two documents. The pointers are RFC 6901 JSON Pointers
+/// into the first (`left`) and the second (`right`) document, and are missing on the
+/// side where the value does not exist.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+/// Flattens a diff into one entry per change. Array changes follow the order of the
+/// elements; within an object, removed keys come first, then added keys, renamed keys,
+/// changed values in the order of `DiffOptions::key_order` and finally a key reordering.
+pub fn changes(json: &JsonV) -> Vec<Change> {
+    let mut output = Vec::new();
+    changes_rec(json, "", "", &mut output);
+
+    output
+}
+
+/// Escapes a key or index for use as a JSON Pointer reference token.
+pub fn pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+pub(crate) fn changes_rec(json: &JsonV, left: &str, right: &str, output: &mut Vec<Change>) {
+    let changed = |kind: ChangeKind| Change {
+        kind,
+        left: Some(left.to_string()),
+        right: Some(right.to_string()),
+        old: Some(json.first_value()),
+        new: Some(json.second_value()),
+    };
+    match json {
+        JsonV::Null(Some(_))
+        | JsonV::Bool(_, Some(_))
+        | JsonV::Number(_, Some(_), _)
+        | JsonV::String(_, Some(_), _)
+        | JsonV::Replaced(_) => output.push(changed(ChangeKind::Changed)),
+        JsonV::TypeChanged(_, _, _) => output.push(changed(ChangeKind::TypeChanged)),
+        JsonV::Object(_, differences, _) => {
+            for d in differences {
+                match d {
+                    ObjectDiff::ObjectKeyPresent(k, v) => output.push(Change {
+                        kind: ChangeKind::Removed,
+                        left: Some(format!("{}/{}", left, pointer_token(k))),
+                        right: None,
+                        old: Some(v.first_value()),
+                        new: None,
+                    }),
+                    ObjectDiff::ObjectKeyMissing(k, v) => output.push(Change {
+                        kind: ChangeKind::Added,
+                        left: None,
+                        right: Some(format!("{}/{}", right, pointer_token(k))),
+                        old: None,
+                        new: Some(v.second_value()),
+                    }),
+                    ObjectDiff::ObjectValueDiff(k, v) => changes_rec(
+                        v,
+                        &format!("{}/{}", left, pointer_token(k)),
+                        &format!("{}/{}", right, pointer_token(k)),
+                        output,
+                    ),
+                    ObjectDiff::ObjectKeyRenamed(old, new, v) => {
+                        let (renamed_left, renamed_right) = (
+                            format!("{}/{}", left, pointer_token(old)),
+                            format!("{}/{}", right, pointer_token(new)),
+                        );
+                        output.push(Change {
+                            kind: ChangeKind::Renamed,
+                            left: Some(renamed_left.to_string()),
+                            right: Some(renamed_right.to_string()),
+                            old: Some(v.first_value()),
+                            new: Some(v.second_value()),
+                        });
+                        changes_rec(v, &renamed_left, &renamed_right, output);
+                    }
+                    ObjectDiff::ObjectKeysReordered(first, second) => output.push(Change {
+                        kind: ChangeKind::Reordered,
+                        left: Some(left.to_string()),
+                        right: Some(right.to_string()),
+                        old: Some(first.iter().map(|x| Value::from(x.as_str())).collect()),
+                        new: Some(second.iter().map(|x| Value::from(x.as_str())).collect()),
+                    }),
+                }
+            }
+        }
+        JsonV::Array(same, differences) => {
+            // Indices into each document advance past the elements present in it
+            let (mut left_index, mut right_index) = (0, 0);
+            for entry in array_entries(same, differences) {
+                match entry {
+                    ArrayEntry::Same(_) => {
+                        left_index += 1;
+                        right_index += 1;
+                    }
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueInFirst(_, v)) => {
+                        output.push(Change {
+                            kind: ChangeKind::Removed,
+                            left: Some(format!("{}/{}", left, left_index)),
+                            right: None,
+                            old: Some(v.first_value()),
+                            new: None,
+                        });
+                        left_index += 1;
+                    }
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueInSecond(_, v)) => {
+                        output.push(Change {
+                            kind: ChangeKind::Added,
+                            left: None,
+                            right: Some(format!("{}/{}", right, right_index)),
+                            old: None,
+                            new: Some(v.second_value()),
+                        });
+                        right_index += 1;
+                    }
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueMovedAway(_, _, _)) => left_index += 1,
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueMoved(_, from, v)) => {
+                        let (moved_left, moved_right) = (
+                            format!("{}/{}", left, from),
+                            format!("{}/{}", right, right_index),
+                        );
+                        output.push(Change {
+                            kind: ChangeKind::Moved,
+                            left: Some(moved_left.to_string()),
+                            right: Some(moved_right.to_string()),
+                            old: Some(v.first_value()),
+                            new: Some(v.second_value()),
+                        });
+                        changes_rec(v, &moved_left, &moved_right, output);
+                        right_index += 1;
+                    }
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueDiff(_, v)) => {
+                        changes_rec(
+                            v,
+                            &format!("{}/{}", left, left_index),
+                            &format!("{}/{}", right, right_index),
+                            output,
+                        );
+                        left_index += 1;
+                        right_index += 1;
+                    }
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::json_diff::diff;
+
+    #[test]
+    fn test_changes() {
+        let r = r#"{"a/b": 1, "list": [1, 2, 3, {"x": "y"}], "gone": 0, "t": "s"}"#;
+        let r2 = r#"{"a/b": 2, "list": [0, 1, 3, {"x": "z"}], "new": 0, "t": [1]}"#;
+        let json = diff(r, r2).unwrap();
+        let summary: Vec<(ChangeKind, Option<String>, Option<String>)> = changes(&json)
+            .into_iter()
+            .map(|x| (x.kind, x.left, x.right))
+            .collect();
+
+        let pointer = |p: &str| Some(p.to_string());
+        assert_eq!(
+            summary,
+            vec![
+                (ChangeKind::Removed, pointer("/gone"), None),
+                (ChangeKind::Added, None, pointer("/new")),
+                (ChangeKind::Changed, pointer("/a~1b"), pointer("/a~1b")),
+                (ChangeKind::Added, None, pointer("/list/0")),
+                (ChangeKind::Removed, pointer("/list/1"), None),
+                (
+                    ChangeKind::Changed,
+                    pointer("/list/3/x"),
+                    pointer("/list/3/x")
+                ),
+                (ChangeKind::TypeChanged, pointer("/t"), pointer("/t")),
+            ]
+        );
+    }
+}
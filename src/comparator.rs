@@ -0,0 +1,35 @@
+use serde_json::Value;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Equal,
+    /// The values are shown as replaced. Two objects or two arrays are still diffed as
+    /// structures to show where they differ, and shown as replaced if they do not.
+    Different,
+    /// Diff the values with the built-in comparison, without consulting later comparators
+    Structural,
+    /// No opinion, the next matching comparator or the built-in comparison decides
+    Undecided,
+}
+
+/// Domain-specific equality for the values at the paths it is registered for in
+/// `DiffOptions::comparators`.
+pub trait Comparator {
+    fn compare(&self, a: &Value, b: &Value) -> Comparison;
+}
+
+impl<F> Comparator for F
+where
+    F: Fn(&Value, &Value) -> Comparison,
+{
+    fn compare(&self, a: &Value, b: &Value) -> Comparison {
+        self(a, b)
+    }
+}
+
+impl fmt::Debug for dyn Comparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Comparator")
+    }
+}
@@ -1,19 +1,14 @@
 use std::fmt;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub enum EditType {
     Insert(usize),
     Delete(usize),
     Substitute(usize, usize, bool),
+    #[default]
     Unknown,
 }
 
-impl Default for EditType {
-    fn default() -> Self {
-        EditType::Unknown
-    }
-}
-
 impl fmt::Display for EditType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let v = match self {
@@ -29,6 +24,17 @@ impl fmt::Display for EditType {
 }
 
 pub fn edit_distance<T: std::cmp::Eq>(arg1: Vec<T>, arg2: Vec<T>) -> Vec<EditType> {
+    edit_distance_with_cost(arg1, arg2, |a, b| if a == b { 0 } else { 2 })
+}
+
+/// Like `edit_distance`, but with the cost of substituting one element for another given
+/// by `substitution_cost`. A cost of 0 means the elements are the same, and since an
+/// insert plus a delete costs 2, only substitutions cheaper than that are ever chosen.
+pub fn edit_distance_with_cost<T, F: Fn(&T, &T) -> usize>(
+    arg1: Vec<T>,
+    arg2: Vec<T>,
+    substitution_cost: F,
+) -> Vec<EditType> {
     let h = arg1.len() + 1;
     let w = arg2.len() + 1;
     let mut edit_matrix: Vec<usize> = vec![Default::default(); w * h];
@@ -49,17 +55,17 @@ pub fn edit_distance<T: std::cmp::Eq>(arg1: Vec<T>, arg2: Vec<T>) -> Vec<EditTyp
     // Compute
     for y in 1..(arg1.len() + 1) {
         for x in 1..(arg2.len() + 1) {
-            let substitution_cost = if arg1[y - 1] == arg2[x - 1] { 0 } else { 2 };
+            let cost = substitution_cost(&arg1[y - 1], &arg2[x - 1]);
             let prefix_dists = vec![
                 edit_matrix[get_array_position(x - 1, y)] + 1,
                 edit_matrix[get_array_position(x, y - 1)] + 1,
-                edit_matrix[get_array_position(x - 1, y - 1)] + substitution_cost,
+                edit_matrix[get_array_position(x - 1, y - 1)] + cost,
             ];
             let prefix_dist_index = min_arg(&prefix_dists);
             let v3 = match prefix_dist_index {
                 0 => EditType::Insert(x - 1),
                 1 => EditType::Delete(y - 1),
-                2 => EditType::Substitute(y - 1, x - 1, substitution_cost == 0),
+                2 => EditType::Substitute(y - 1, x - 1, cost == 0),
                 _ => EditType::Unknown,
             };
             backtrack[get_array_position(x, y)] = v3;
@@ -85,7 +91,85 @@ pub fn edit_distance<T: std::cmp::Eq>(arg1: Vec<T>, arg2: Vec<T>) -> Vec<EditTyp
     output
 }
 
-fn min_arg<T: std::cmp::Ord + std::clone::Clone>(v: &Vec<T>) -> usize {
+/// Like `edit_distance`, but in memory linear in the length of the inputs, using
+/// Hirschberg's algorithm. Only equal elements are substituted.
+pub fn edit_distance_linear<T: std::cmp::Eq>(arg1: &[T], arg2: &[T]) -> Vec<EditType> {
+    let mut output: Vec<EditType> = Vec::new();
+    hirschberg(arg1, arg2, (0, 0), &mut output);
+
+    output
+}
+
+fn hirschberg<T: std::cmp::Eq>(
+    arg1: &[T],
+    arg2: &[T],
+    (offset1, offset2): (usize, usize),
+    output: &mut Vec<EditType>,
+) {
+    let inserts = |range: std::ops::Range<usize>| range.map(move |j| EditType::Insert(offset2 + j));
+    match arg1 {
+        [] => output.extend(inserts(0..arg2.len())),
+        [x] => match arg2.iter().position(|y| x == y) {
+            Some(j) => {
+                output.extend(inserts(0..j));
+                output.push(EditType::Substitute(offset1, offset2 + j, true));
+                output.extend(inserts(j + 1..arg2.len()));
+            }
+            None => {
+                output.push(EditType::Delete(offset1));
+                output.extend(inserts(0..arg2.len()));
+            }
+        },
+        _ if arg2.is_empty() => {
+            output.extend((0..arg1.len()).map(|i| EditType::Delete(offset1 + i)))
+        }
+        _ => {
+            // Split the second sequence where the halves of the first keep the most in common
+            let middle = arg1.len() / 2;
+            let forward = common_lengths(arg1[..middle].iter(), arg2.iter());
+            let backward = common_lengths(arg1[middle..].iter().rev(), arg2.iter().rev());
+            let split = (0..=arg2.len())
+                .max_by_key(|j| {
+                    (
+                        forward[*j] + backward[arg2.len() - j],
+                        std::cmp::Reverse(*j),
+                    )
+                })
+                .unwrap_or(0);
+            hirschberg(&arg1[..middle], &arg2[..split], (offset1, offset2), output);
+            hirschberg(
+                &arg1[middle..],
+                &arg2[split..],
+                (offset1 + middle, offset2 + split),
+                output,
+            );
+        }
+    }
+}
+
+/// The length of the longest common subsequence of `arg1` and each prefix of `arg2`.
+fn common_lengths<'a, T: std::cmp::Eq + 'a>(
+    arg1: impl Iterator<Item = &'a T>,
+    arg2: impl Iterator<Item = &'a T> + Clone,
+) -> Vec<usize> {
+    let mut row = vec![0; arg2.clone().count() + 1];
+    for x in arg1 {
+        let mut diagonal = 0;
+        for (j, y) in arg2.clone().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if x == y {
+                diagonal + 1
+            } else {
+                above.max(row[j])
+            };
+            diagonal = above;
+        }
+    }
+
+    row
+}
+
+fn min_arg<T: std::cmp::Ord + std::clone::Clone>(v: &[T]) -> usize {
     v.iter()
         .enumerate()
         .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
@@ -102,4 +186,55 @@ mod tests {
         let res = edit_distance("2334".chars().collect(), "1223344".chars().collect());
         println!("{:?}", res);
     }
+
+    #[test]
+    fn test_edit_distance_with_cost() {
+        let res = edit_distance_with_cost(vec![1, 5, 3], vec![1, 6, 3], |a, b| {
+            if a == b {
+                0
+            } else {
+                1
+            }
+        });
+        assert_eq!(
+            format!("{:?}", res),
+            "[Substitute(0, 0, true), Substitute(1, 1, false), Substitute(2, 2, true)]"
+        );
+    }
+
+    #[test]
+    fn test_edit_distance_linear() {
+        let (arg1, arg2): (Vec<char>, Vec<char>) = (
+            "ACCGGTCGAGTGCGCGGAAGCCGGCCGAA".chars().collect(),
+            "GTCGTTCGGAATGCCGTTGCTCTGTAAA".chars().collect(),
+        );
+        let res = edit_distance_linear(&arg1, &arg2);
+        let same = |edits: &[EditType]| {
+            edits
+                .iter()
+                .filter(|x| matches!(x, EditType::Substitute(_, _, true)))
+                .count()
+        };
+        assert_eq!(same(&res), 20);
+        assert_eq!(same(&res), same(&edit_distance(arg1.clone(), arg2.clone())));
+
+        // Replaying the edits rebuilds the second sequence from the first
+        let (mut i, mut rebuilt) = (0, Vec::new());
+        for edit in res {
+            match edit {
+                EditType::Insert(j) => rebuilt.push(arg2[j]),
+                EditType::Delete(x) => {
+                    assert_eq!(x, i);
+                    i += 1;
+                }
+                EditType::Substitute(x, j, _) => {
+                    assert_eq!((x, arg1[x]), (i, arg2[j]));
+                    rebuilt.push(arg1[x]);
+                    i += 1;
+                }
+                EditType::Unknown => panic!("unexpected edit"),
+            }
+        }
+        assert_eq!((i, rebuilt), (arg1.len(), arg2));
+    }
 }
@@ -0,0 +1,123 @@
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The first document is not valid json
+    LeftParse(ParseError),
+    /// The second document is not valid json
+    RightParse(ParseError),
+    /// An input exceeds one of the limits in `DiffOptions`
+    Limit(String),
+    /// A change that the requested output cannot express, with the JSON Pointer to it
+    /// and the reason
+    Unrepresentable(String, String),
+    /// A patch operation that cannot be applied
+    Apply(ApplyError),
+    /// Reading a streamed document failed
+    Io(String),
+    /// A path pattern that cannot be parsed, with the reason
+    Pattern(String, String),
+    /// An option the operation does not support, with its name
+    Unsupported(String),
+    Internal(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// 1-based, as reported by serde_json
+    pub line: usize,
+    /// 1-based, as reported by serde_json
+    pub column: usize,
+    /// The text around the error position
+    pub snippet: String,
+}
+
+impl ParseError {
+    pub fn new(error: &serde_json::Error, input: &str) -> ParseError {
+        ParseError {
+            message: error.to_string(),
+            line: error.line(),
+            column: error.column(),
+            snippet: snippet(input, error.line(), error.column()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApplyError {
+    /// The index of the failing operation in the patch
+    pub op_index: usize,
+    pub pointer: String,
+    pub reason: String,
+}
+
+/// Up to 20 characters on either side of the position, on the same line.
+fn snippet(input: &str, line: usize, column: usize) -> String {
+    let source_line: Vec<char> = input
+        .lines()
+        .nth(line.saturating_sub(1))
+        .unwrap_or("")
+        .chars()
+        .collect();
+    let end = column.min(source_line.len());
+    let start = end.saturating_sub(20);
+    source_line[start..(end + 20).min(source_line.len())]
+        .iter()
+        .collect()
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::LeftParse(e) => write!(f, "Invalid json in the first document: {}", e),
+            Error::RightParse(e) => write!(f, "Invalid json in the second document: {}", e),
+            Error::Limit(message) => write!(f, "Limit exceeded: {}", message),
+            Error::Unrepresentable(pointer, reason) => {
+                write!(
+                    f,
+                    "Cannot represent the change at `{}`: {}",
+                    pointer, reason
+                )
+            }
+            Error::Apply(e) => write!(
+                f,
+                "Cannot apply operation {} at `{}`: {}",
+                e.op_index, e.pointer, e.reason
+            ),
+            Error::Io(message) => write!(f, "Cannot read the input: {}", message),
+            Error::Pattern(pattern, reason) => {
+                write!(f, "Invalid path pattern `{}`: {}", pattern, reason)
+            }
+            Error::Unsupported(option) => write!(f, "Unsupported option: {}", option),
+            Error::Internal(message) => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} near `{}`", self.message, self.snippet)
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_parse_error_position() {
+        let input = "{\n  \"a\": 1,\n  \"b\": tru,\n  \"c\": 2\n}";
+        let error = serde_json::from_str::<serde_json::Value>(input).unwrap_err();
+        let parse_error = ParseError::new(&error, input);
+
+        assert_eq!(parse_error.line, 3);
+        assert_eq!(parse_error.column, 11);
+        assert_eq!(parse_error.snippet, "  \"b\": tru,");
+    }
+}
@@ -1,13 +1,21 @@
-use super::json_diff::{ArrayDiff, JsonV, ObjectDiff};
+use super::json_diff::{ArrayDiff, JsonV, ObjectDiff, StringSegment};
 use std::clone::Clone;
-use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::fmt;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone)]
 enum Line {
     Same(usize, String),
     DiffMissing(usize, String),
     DiffPresent(usize, String),
+    Ignored(usize, String),
+    Segments(usize, Vec<StringSegment>),
+    /// An unchanged container shown as a summary, with the html of its content if it
+    /// can be expanded
+    Collapsed(usize, String, Option<String>),
+    Moved(usize, String),
+    Renamed(usize, String),
     NewLine,
     Text(usize, String),
     Start,
@@ -16,9 +24,21 @@ enum Line {
 impl fmt::Display for Line {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = match self {
+            Line::Segments(_, segments) => {
+                let texts = segments.iter().map(|x| match x {
+                    StringSegment::Same(t) => t,
+                    StringSegment::InFirst(t) => t,
+                    StringSegment::InSecond(t) => t,
+                });
+                return write!(f, "{}", texts.cloned().collect::<String>());
+            }
             Line::Same(_, x) => x,
             Line::DiffMissing(_, x) => x,
             Line::DiffPresent(_, x) => x,
+            Line::Ignored(_, x) => x,
+            Line::Collapsed(_, x, _) => x,
+            Line::Moved(_, x) => x,
+            Line::Renamed(_, x) => x,
             Line::Text(_, s) => s,
             Line::Start => "",
             Line::NewLine => "\n",
@@ -27,6 +47,8 @@ impl fmt::Display for Line {
     }
 }
 
+type LineType = fn(usize, String) -> Line;
+
 #[derive(Debug)]
 enum Either<L, R> {
     Left(L),
@@ -54,16 +76,31 @@ impl fmt::Display for Node {
     }
 }
 
+#[derive(Clone, Debug, Default)]
+pub struct RenderOptions {
+    /// Show unchanged objects and arrays, and runs of unchanged scalar keys next to
+    /// changed ones, as a single summary line.
+    pub collapse_unchanged: bool,
+    /// The number of levels of an unchanged subtree shown before it is collapsed.
+    pub context_depth: usize,
+    /// Include the content of collapsed regions, hidden until expanded. The content is
+    /// embedded in the page, so this saves no space over not collapsing at all.
+    pub expandable: bool,
+}
+
 pub fn generate(json: JsonV) -> String {
+    generate_with_options(json, &RenderOptions::default())
+}
+
+pub fn generate_with_options(json: JsonV, options: &RenderOptions) -> String {
     let start_node = Node {
         previous: None,
         content: Line::Start,
     };
-    let last_node = generate_rec(0, json, start_node, None);
-
-    let html = to_html(last_node);
+    let context = options.collapse_unchanged.then_some(options.context_depth);
+    let last_node = generate_rec(0, json, start_node, None, options, context);
 
-    html
+    to_html(last_node)
 }
 
 fn text_node(previous: Node, text: String, indent: usize) -> Node {
@@ -73,6 +110,13 @@ fn text_node(previous: Node, text: String, indent: usize) -> Node {
     }
 }
 
+fn moved_node(previous: Node, text: String, indent: usize) -> Node {
+    Node {
+        previous: Some(Box::new(previous)),
+        content: Line::Moved(indent, text),
+    }
+}
+
 fn newline_node(previous: Node) -> Node {
     Node {
         previous: Some(Box::new(previous)),
@@ -80,44 +124,100 @@ fn newline_node(previous: Node) -> Node {
     }
 }
 
+/// `collapse` is the number of levels of unchanged containers left to show before
+/// collapsing them, or None to show everything.
 fn generate_rec(
     indent: usize,
     json: JsonV,
     last_node: Node,
     type_to_use: Option<fn(usize, String) -> Line>,
+    options: &RenderOptions,
+    collapse: Option<usize>,
 ) -> Node {
+    // Changed values restart the context around them
+    let context = options.collapse_unchanged.then_some(options.context_depth);
     match json {
+        JsonV::Object(h, st, keys) if st.is_empty() && collapse == Some(0) => {
+            let summary = format!("{{\u{2026}{} unchanged}}", plural(h.len(), "key"));
+            collapsed(
+                indent,
+                JsonV::Object(h, st, keys),
+                summary,
+                last_node,
+                type_to_use,
+                options,
+            )
+        }
+        JsonV::Array(v, differences) if differences.is_empty() && collapse == Some(0) => {
+            let summary = format!("[\u{2026}{} unchanged]", plural(v.len(), "element"));
+            collapsed(
+                indent,
+                JsonV::Array(v, differences),
+                summary,
+                last_node,
+                type_to_use,
+                options,
+            )
+        }
         JsonV::Null(st) => {
             if let Some(o) = st {
-                let mut curr_node =
-                    generate_rec(indent, o.0, last_node, Some(|i, x| Line::DiffPresent(i, x)));
+                let mut curr_node = generate_rec(
+                    indent,
+                    o.0,
+                    last_node,
+                    Some(Line::DiffPresent),
+                    options,
+                    None,
+                );
                 curr_node = text_node(curr_node, ", ".to_string(), indent);
                 curr_node = newline_node(curr_node);
-                curr_node =
-                    generate_rec(indent, o.1, curr_node, Some(|i, x| Line::DiffMissing(i, x)));
+                curr_node = generate_rec(
+                    indent,
+                    o.1,
+                    curr_node,
+                    Some(Line::DiffMissing),
+                    options,
+                    None,
+                );
                 curr_node
             } else {
                 Node {
                     previous: Some(Box::new(last_node)),
-                    content: type_to_use.unwrap_or(|i, x| Line::Same(i, x))(
+                    content: type_to_use.unwrap_or(Line::Same)(
                         indent,
                         "null".to_string(),
                     ),
                 }
             }
         }
-        JsonV::String(s, st) => {
+        JsonV::String(_, Some(_), segments) if !segments.is_empty() => Node {
+            previous: Some(Box::new(last_node)),
+            content: Line::Segments(indent, segments),
+        },
+        JsonV::String(s, st, _) => {
             if let Some(o) = st {
-                let mut curr_node =
-                    generate_rec(indent, o.0, last_node, Some(|i, x| Line::DiffPresent(i, x)));
+                let mut curr_node = generate_rec(
+                    indent,
+                    o.0,
+                    last_node,
+                    Some(Line::DiffPresent),
+                    options,
+                    None,
+                );
                 curr_node = text_node(curr_node, ", ".to_string(), indent);
-                curr_node =
-                    generate_rec(indent, o.1, curr_node, Some(|i, x| Line::DiffMissing(i, x)));
+                curr_node = generate_rec(
+                    indent,
+                    o.1,
+                    curr_node,
+                    Some(Line::DiffMissing),
+                    options,
+                    None,
+                );
                 curr_node
             } else {
                 Node {
                     previous: Some(Box::new(last_node)),
-                    content: type_to_use.unwrap_or(|i, x| Line::Same(i, x))(
+                    content: type_to_use.unwrap_or(Line::Same)(
                         indent,
                         format!("\"{}\"", s),
                     ),
@@ -127,34 +227,58 @@ fn generate_rec(
         JsonV::Bool(b, st) => {
             let bool_string = if b { "true" } else { "false" };
             if let Some(o) = st {
-                let mut curr_node =
-                    generate_rec(indent, o.0, last_node, Some(|i, x| Line::DiffPresent(i, x)));
+                let mut curr_node = generate_rec(
+                    indent,
+                    o.0,
+                    last_node,
+                    Some(Line::DiffPresent),
+                    options,
+                    None,
+                );
                 curr_node = text_node(curr_node, ", ".to_string(), indent);
-                curr_node =
-                    generate_rec(indent, o.1, curr_node, Some(|i, x| Line::DiffMissing(i, x)));
+                curr_node = generate_rec(
+                    indent,
+                    o.1,
+                    curr_node,
+                    Some(Line::DiffMissing),
+                    options,
+                    None,
+                );
                 curr_node
             } else {
                 Node {
                     previous: Some(Box::new(last_node)),
-                    content: type_to_use.unwrap_or(|i, x| Line::Same(i, x))(
+                    content: type_to_use.unwrap_or(Line::Same)(
                         indent,
                         bool_string.to_string(),
                     ),
                 }
             }
         }
-        JsonV::Number(n, st) => {
+        JsonV::Number(n, st, _) => {
             if let Some(o) = st {
-                let mut curr_node =
-                    generate_rec(indent, o.0, last_node, Some(|i, x| Line::DiffPresent(i, x)));
+                let mut curr_node = generate_rec(
+                    indent,
+                    o.0,
+                    last_node,
+                    Some(Line::DiffPresent),
+                    options,
+                    None,
+                );
                 curr_node = text_node(curr_node, ", ".to_string(), indent);
-                curr_node =
-                    generate_rec(indent, o.1, curr_node, Some(|i, x| Line::DiffMissing(i, x)));
+                curr_node = generate_rec(
+                    indent,
+                    o.1,
+                    curr_node,
+                    Some(Line::DiffMissing),
+                    options,
+                    None,
+                );
                 curr_node
             } else {
                 Node {
                     previous: Some(Box::new(last_node)),
-                    content: type_to_use.unwrap_or(|i, x| Line::Same(i, x))(indent, n.to_string()),
+                    content: type_to_use.unwrap_or(Line::Same)(indent, n.to_string()),
                 }
             }
         }
@@ -164,18 +288,46 @@ fn generate_rec(
                 previous: Some(Box::new(curr_node)),
                 content: Line::NewLine,
             };
+            let same_collapse = if differences.is_empty() {
+                collapse.map(|x| x - 1)
+            } else {
+                context
+            };
             let elements = get_array_elements_in_order(v, differences);
             for e in elements {
-                let (element, type_of_line): (JsonV, fn(usize, String) -> Line) = match e {
-                    Either::Left((_, element)) => (element, type_to_use.unwrap_or(|i, x| Line::Same(i, x))),
-                    Either::Right(ArrayDiff::ArrayValueInFirst(_, element)) => (element, |i, x| Line::DiffPresent(i, x)),
-                    Either::Right(ArrayDiff::ArrayValueInSecond(_, element)) => (element, |i, x| Line::DiffMissing(i, x)),
+                let (element, type_of_line, child_collapse): (JsonV, LineType, _) = match e {
+                    Either::Left((_, element)) => {
+                        (element, type_to_use.unwrap_or(Line::Same), same_collapse)
+                    }
+                    Either::Right(ArrayDiff::ArrayValueInFirst(_, element)) => {
+                        (element, Line::DiffPresent, None)
+                    }
+                    Either::Right(ArrayDiff::ArrayValueInSecond(_, element)) => {
+                        (element, Line::DiffMissing, None)
+                    }
+                    Either::Right(ArrayDiff::ArrayValueDiff(_, element)) => {
+                        (element, Line::Same, context)
+                    }
+                    Either::Right(ArrayDiff::ArrayValueMovedAway(_, to, _)) => {
+                        let marker = format!("(moved to {})", to);
+                        curr_node = moved_node(curr_node, marker, indent + 1);
+                        curr_node = text_node(curr_node, ", ".to_string(), indent);
+                        curr_node = newline_node(curr_node);
+                        continue;
+                    }
+                    Either::Right(ArrayDiff::ArrayValueMoved(_, from, element)) => {
+                        let marker = format!("(moved from {}) ", from);
+                        curr_node = moved_node(curr_node, marker, indent + 1);
+                        (element, Line::Same, context)
+                    }
                 };
                 curr_node = generate_rec(
                     indent + 1,
                     element,
                     curr_node,
                     Some(type_of_line),
+                    options,
+                    child_collapse,
                 );
                 curr_node = text_node(curr_node, ", ".to_string(), indent);
                 curr_node = newline_node(curr_node);
@@ -183,40 +335,96 @@ fn generate_rec(
 
             text_node(curr_node, "]".to_string(), indent)
         }
-        JsonV::Object(h, st) => {
+        JsonV::Object(h, st, keys) => {
             let mut curr_node = text_node(last_node, "{".to_string(), indent);
             curr_node = newline_node(curr_node);
-            for (k, v) in h {
-                curr_node = text_node(curr_node, format!("{}: ", k), indent + 1);
-                curr_node = generate_rec(indent + 1, v, curr_node, type_to_use);
-                curr_node = text_node(curr_node, ", ".to_string(), indent + 1);
-                curr_node = newline_node(curr_node);
-            }
-            for m in st {
-                match m {
-                    ObjectDiff::ObjectKeyMissing(s, v) => {
+            let same_collapse = if st.is_empty() {
+                collapse.map(|x| x - 1)
+            } else {
+                context
+            };
+            let collapse_scalars = options.collapse_unchanged && !st.is_empty();
+            // Unchanged and changed keys in the key order of the diff, then the rest
+            let ranks: HashMap<&str, usize> = keys
+                .iter()
+                .enumerate()
+                .map(|(i, k)| (k.as_str(), i))
+                .collect();
+            let rank = |k: &str| ranks.get(k).copied().unwrap_or(usize::MAX);
+            let mut entries: Vec<Either<(String, JsonV), ObjectDiff>> = h
+                .into_iter()
+                .map(Either::Left)
+                .chain(st.into_iter().map(Either::Right))
+                .collect();
+            entries.sort_by_key(|entry| match entry {
+                Either::Left((k, _))
+                | Either::Right(ObjectDiff::ObjectKeyMissing(k, _))
+                | Either::Right(ObjectDiff::ObjectKeyPresent(k, _))
+                | Either::Right(ObjectDiff::ObjectValueDiff(k, _)) => rank(k),
+                Either::Right(ObjectDiff::ObjectKeyRenamed(old, new, _)) => {
+                    rank(old).min(rank(new))
+                }
+                Either::Right(ObjectDiff::ObjectKeysReordered(_, _)) => usize::MAX,
+            });
+            let is_unchanged_scalar = |entry: &Either<(String, JsonV), ObjectDiff>| {
+                matches!(entry, Either::Left((_, v)) if is_primitive_json_type(v))
+            };
+            let mut entries = entries.into_iter().peekable();
+            while let Some(entry) = entries.next() {
+                match entry {
+                    // Runs of unchanged scalars are collapsed to a single line
+                    Either::Left(first)
+                        if collapse_scalars
+                            && is_primitive_json_type(&first.1)
+                            && entries.peek().is_some_and(is_unchanged_scalar) =>
+                    {
+                        let mut run = vec![first];
+                        while let Some(Either::Left(x)) = entries.next_if(is_unchanged_scalar) {
+                            run.push(x);
+                        }
+                        curr_node = collapsed_keys(indent + 1, run, curr_node, options);
+                        curr_node = newline_node(curr_node);
+                    }
+                    Either::Left((k, v)) => {
+                        curr_node = text_node(curr_node, format!("{}: ", k), indent + 1);
+                        curr_node = generate_rec(
+                            indent + 1,
+                            v,
+                            curr_node,
+                            type_to_use,
+                            options,
+                            same_collapse,
+                        );
+                        curr_node = text_node(curr_node, ", ".to_string(), indent + 1);
+                        curr_node = newline_node(curr_node);
+                    }
+                    Either::Right(ObjectDiff::ObjectKeyMissing(s, v)) => {
                         curr_node = text_node(curr_node, format!("{}: ", s), indent + 1);
                         curr_node = generate_rec(
                             indent + 1,
                             v,
                             curr_node,
-                            Some(|i, x| Line::DiffMissing(i, x)),
+                            Some(Line::DiffMissing),
+                            options,
+                            None,
                         );
                         curr_node = text_node(curr_node, ", ".to_string(), indent + 1);
                         curr_node = newline_node(curr_node);
                     }
-                    ObjectDiff::ObjectKeyPresent(s, v) => {
+                    Either::Right(ObjectDiff::ObjectKeyPresent(s, v)) => {
                         curr_node = text_node(curr_node, format!("{}: ", s), indent + 1);
                         curr_node = generate_rec(
                             indent + 1,
                             v,
                             curr_node,
-                            Some(|i, x| Line::DiffPresent(i, x)),
+                            Some(Line::DiffPresent),
+                            options,
+                            None,
                         );
                         curr_node = text_node(curr_node, ", ".to_string(), indent + 1);
                         curr_node = newline_node(curr_node);
                     }
-                    ObjectDiff::ObjectValueDiff(s, v) => {
+                    Either::Right(ObjectDiff::ObjectValueDiff(s, v)) => {
                         let addon = if is_primitive_json_type(&v) {"###"} else {""};
                         curr_node = text_node(curr_node, format!("{}: {}", s, addon), indent + 1);
                         curr_node = newline_node(curr_node);
@@ -224,29 +432,166 @@ fn generate_rec(
                             indent + 1,
                             v,
                             curr_node,
-                            Some(|i, x| Line::Same(i, x)),
+                            Some(Line::Same),
+                            options,
+                            context,
                         );
                         curr_node = newline_node(curr_node);
                         curr_node = text_node(curr_node, format!("{}, ", addon), indent + 1);
                         curr_node = newline_node(curr_node);
                     }
+                    Either::Right(ObjectDiff::ObjectKeyRenamed(old, new, v)) => {
+                        curr_node = text_node(curr_node, format!("{}: ", new), indent + 1);
+                        curr_node = Node {
+                            previous: Some(Box::new(curr_node)),
+                            content: Line::Renamed(indent + 1, format!("(renamed from {}) ", old)),
+                        };
+                        curr_node = generate_rec(
+                            indent + 1,
+                            v,
+                            curr_node,
+                            Some(Line::Same),
+                            options,
+                            context,
+                        );
+                        curr_node = text_node(curr_node, ", ".to_string(), indent + 1);
+                        curr_node = newline_node(curr_node);
+                    }
+                    Either::Right(ObjectDiff::ObjectKeysReordered(first, second)) => {
+                        curr_node = text_node(curr_node, "(key order): ".to_string(), indent + 1);
+                        curr_node = Node {
+                            previous: Some(Box::new(curr_node)),
+                            content: Line::DiffPresent(indent + 1, first.join(", ")),
+                        };
+                        curr_node = text_node(curr_node, ", ".to_string(), indent + 1);
+                        curr_node = Node {
+                            previous: Some(Box::new(curr_node)),
+                            content: Line::DiffMissing(indent + 1, second.join(", ")),
+                        };
+                        curr_node = newline_node(curr_node);
+                    }
                 }
             }
             text_node(curr_node, "}".to_string(), indent)
         }
+        JsonV::Ignored(v) => generate_rec(
+            indent,
+            *v,
+            last_node,
+            Some(Line::Ignored),
+            options,
+            collapse,
+        ),
+        JsonV::TypeChanged(first_type, second_type, o) => {
+            let description = format!("({} to {}) ", first_type, second_type);
+            replaced(indent, description, *o, last_node, options)
+        }
+        JsonV::Replaced(o) => replaced(indent, "(replaced) ".to_string(), *o, last_node, options),
     }
 }
 
-fn is_primitive_json_type(j: &JsonV) -> bool {
-    match j {
-        JsonV::Null(_) => true,
-        JsonV::Bool(_, _) => true,
-        JsonV::Number(_, _) => true,
-        JsonV::String(_, _) => true,
-        _ => false
+/// Both values, the first one shown as present and the second as missing.
+fn replaced(
+    indent: usize,
+    description: String,
+    o: (JsonV, JsonV),
+    last_node: Node,
+    options: &RenderOptions,
+) -> Node {
+    let mut curr_node = text_node(last_node, description, indent);
+    curr_node = generate_rec(
+        indent,
+        o.0,
+        curr_node,
+        Some(Line::DiffPresent),
+        options,
+        None,
+    );
+    curr_node = text_node(curr_node, ", ".to_string(), indent);
+    curr_node = newline_node(curr_node);
+    generate_rec(
+        indent,
+        o.1,
+        curr_node,
+        Some(Line::DiffMissing),
+        options,
+        None,
+    )
+}
+
+fn collapsed(
+    indent: usize,
+    json: JsonV,
+    summary: String,
+    last_node: Node,
+    type_to_use: Option<fn(usize, String) -> Line>,
+    options: &RenderOptions,
+) -> Node {
+    let content = if options.expandable {
+        let start_node = Node {
+            previous: None,
+            content: Line::Start,
+        };
+        // Indented relative to the line holding the summary
+        Some(to_html(generate_rec(
+            0,
+            json,
+            start_node,
+            type_to_use,
+            options,
+            None,
+        )))
+    } else {
+        None
+    };
+    Node {
+        previous: Some(Box::new(last_node)),
+        content: Line::Collapsed(indent, summary, content),
+    }
+}
+
+/// Consecutive unchanged keys of an object shown as a summary.
+fn collapsed_keys(
+    indent: usize,
+    run: Vec<(String, JsonV)>,
+    last_node: Node,
+    options: &RenderOptions,
+) -> Node {
+    let summary = format!("\u{2026}{} unchanged", plural(run.len(), "key"));
+    let content = options.expandable.then(|| {
+        let mut curr_node = Node {
+            previous: None,
+            content: Line::Start,
+        };
+        for (k, v) in run {
+            curr_node = text_node(curr_node, format!("{}: ", k), 0);
+            curr_node = generate_rec(0, v, curr_node, None, options, None);
+            curr_node = text_node(curr_node, ", ".to_string(), 0);
+            curr_node = newline_node(curr_node);
+        }
+        to_html(curr_node)
+    });
+    Node {
+        previous: Some(Box::new(last_node)),
+        content: Line::Collapsed(indent, summary, content),
     }
 }
 
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+fn is_primitive_json_type(j: &JsonV) -> bool {
+    matches!(
+        j,
+        JsonV::Null(_)
+            | JsonV::Bool(_, _)
+            | JsonV::Number(_, _, _)
+            | JsonV::String(_, _, _)
+            | JsonV::TypeChanged(_, _, _)
+    )
+}
+
 type SortNode = Either<(usize, JsonV), ArrayDiff>;
 
 fn get_array_elements_in_order(
@@ -266,6 +611,9 @@ fn get_array_elements_in_order(
             Either::Left((i, _)) => *i,
             Either::Right(ArrayDiff::ArrayValueInFirst(i, _)) => *i,
             Either::Right(ArrayDiff::ArrayValueInSecond(i, _)) => *i,
+            Either::Right(ArrayDiff::ArrayValueDiff(i, _)) => *i,
+            Either::Right(ArrayDiff::ArrayValueMovedAway(i, _, _)) => *i,
+            Either::Right(ArrayDiff::ArrayValueMoved(i, _, _)) => *i,
         }
     };
     all_elements.sort_by(|a, b| get_array_index(a).partial_cmp(&get_array_index(b)).unwrap());
@@ -288,7 +636,7 @@ fn to_html(inp_node: Node) -> String {
                 match text_element {
                     L2::Text(text) => current_line.push((indent, text)),
                     L2::Newline => {
-                        if current_line.len() > 0 {
+                        if !current_line.is_empty() {
                             // Since inp_node start from the bottom-left the line should be flipped
                             current_line.reverse();
                             lines.push(current_line);
@@ -301,7 +649,7 @@ fn to_html(inp_node: Node) -> String {
             maybe_node = node.previous;
         }
     }
-    if current_line.len() > 0 {
+    if !current_line.is_empty() {
         lines.push(current_line);
     }
     // Since inp_node start from the bottom the lines should be reversed
@@ -322,7 +670,7 @@ fn to_html(inp_node: Node) -> String {
                 .join("");
             output_html.push_str(&format!(
                 "<div style=\"margin-left:{}px\">{}</div>",
-                (30 * indent).to_string(),
+                30 * indent,
                 concatenated_line_elements
             ));
         } else {
@@ -341,6 +689,13 @@ fn generate_line(line: Line) -> Option<(usize, L2)> {
         Line::DiffPresent(indent, text) => Some((indent, L2::Text(present(text)))),
         Line::NewLine => Some((0, L2::Newline)),
         Line::Same(indent, text) => Some((indent, L2::Text(same(text)))),
+        Line::Ignored(indent, text) => Some((indent, L2::Text(ignored(text)))),
+        Line::Segments(indent, segments) => Some((indent, L2::Text(string_segments(segments)))),
+        Line::Collapsed(indent, summary, content) => {
+            Some((indent, L2::Text(collapsed_html(summary, content))))
+        }
+        Line::Moved(indent, text) => Some((indent, L2::Text(moved(text)))),
+        Line::Renamed(indent, text) => Some((indent, L2::Text(renamed(text)))),
         Line::Start => None,
         Line::Text(indent, text) => Some((indent, L2::Text(same(text)))),
     }
@@ -350,6 +705,55 @@ fn same(s: String) -> String {
     format!("<span class=\"same\" >{}</span>\n", s).to_string()
 }
 
+fn ignored(s: String) -> String {
+    format!(
+        "<span class=\"ignored\" style=\"color:grey\">{}</span>\n",
+        s
+    )
+}
+
+fn moved(s: String) -> String {
+    format!(
+        "<span class=\"moved\" style=\"color:orange\">{}</span>\n",
+        s
+    )
+}
+
+fn renamed(s: String) -> String {
+    format!(
+        "<span class=\"renamed\" style=\"color:purple\">{}</span>\n",
+        s
+    )
+}
+
+fn collapsed_html(summary: String, content: Option<String>) -> String {
+    match content {
+        Some(html) => format!(
+            "<details class=\"collapsed\" style=\"display:inline-block\"><summary style=\"color:grey\">{}</summary>{}</details>\n",
+            summary, html
+        ),
+        None => format!("<span class=\"collapsed\" style=\"color:grey\">{}</span>\n", summary),
+    }
+}
+
+/// A changed string on a single line, with only the changed segments highlighted.
+fn string_segments(segments: Vec<StringSegment>) -> String {
+    let inner: String = segments
+        .into_iter()
+        .map(|x| match x {
+            StringSegment::Same(t) => t,
+            StringSegment::InFirst(t) => {
+                format!("<span class=\"present\" style=\"color:green\">{}</span>", t)
+            }
+            StringSegment::InSecond(t) => format!(
+                "<span class=\"missing\" style=\"color:red;text-decoration:line-through\">{}</span>",
+                t
+            ),
+        })
+        .collect();
+    format!("<span class=\"changed\" >\"{}\"</span>\n", inner)
+}
+
 fn present(s: String) -> String {
     format!(
         "<span class=\"present\" style=\"color:green\">+++{}+++</span>\n",
@@ -370,22 +774,24 @@ fn missing(s: String) -> String {
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use indexmap::IndexMap;
+
     #[test]
     fn test_generate_html() {
-        let mut map1: BTreeMap<String, JsonV> = BTreeMap::new();
-        let mut map2: BTreeMap<String, JsonV> = BTreeMap::new();
+        let mut map1: IndexMap<String, JsonV> = IndexMap::new();
+        let mut map2: IndexMap<String, JsonV> = IndexMap::new();
         map2.insert(
             "item5".to_string(),
-            JsonV::String("value5".to_string(), None),
+            JsonV::String("value5".to_string(), None, vec![]),
         );
 
         map1.insert(
             "item1".to_string(),
-            JsonV::String("value1".to_string(), None),
+            JsonV::String("value1".to_string(), None, vec![]),
         );
         map1.insert(
             "item2".to_string(),
-            JsonV::String("value2".to_string(), None),
+            JsonV::String("value2".to_string(), None, vec![]),
         );
         map1.insert(
             "item3".to_string(),
@@ -401,23 +807,24 @@ mod tests {
                         ))),
                     ),
                 )],
+                Vec::new(),
             ),
         );
         map1.insert(
             "item5".to_string(),
             JsonV::Array(
                 vec![
-                    (0, JsonV::Number(10.0, None)),
-                    (1, JsonV::Number(20.0, None)),
+                    (0, JsonV::Number(10.into(), None, None)),
+                    (1, JsonV::Number(20.into(), None, None)),
                 ],
                 vec![ArrayDiff::ArrayValueInFirst(
                     0,
-                    JsonV::String("arr".to_string(), None),
+                    JsonV::String("arr".to_string(), None, vec![]),
                 )],
             ),
         );
 
-        let json = JsonV::Object(map1, Vec::new());
+        let json = JsonV::Object(map1, Vec::new(), Vec::new());
         println!("{:?}\n", json);
         let res = generate(json);
 
@@ -426,10 +833,10 @@ mod tests {
 
     #[test]
     fn test_keep_array_order() {
-        let arr = vec![(1, JsonV::String("test2".to_string(), None))];
+        let arr = vec![(1, JsonV::String("test2".to_string(), None, vec![]))];
         let d = vec![ArrayDiff::ArrayValueInFirst(
             0,
-            JsonV::String("test1".to_string(), None),
+            JsonV::String("test1".to_string(), None, vec![]),
         )];
         let res = get_array_elements_in_order(
             arr,
@@ -439,13 +846,82 @@ mod tests {
         let exp: Vec<Either<(usize, JsonV), ArrayDiff>> = vec![
             Either::Right(ArrayDiff::ArrayValueInFirst(
                 0,
-                JsonV::String("test1".to_string(), None),
+                JsonV::String("test1".to_string(), None, vec![]),
             )),
-            Either::Left((1, JsonV::String("test2".to_string(), None))),
+            Either::Left((1, JsonV::String("test2".to_string(), None, vec![]))),
         ];
 
-        println!("{}", format!("{:?}", res));
-        println!("{}", format!("{:?}", exp));
+        println!("{:?}", res);
+        println!("{:?}", exp);
         assert_eq!(format!("{:?}", res), format!("{:?}", exp));
     }
+
+    #[test]
+    fn test_collapse_unchanged() {
+        let json = crate::json_diff::diff(
+            r#"{"a": {"b": {"c": 1}}, "l": [1, 2], "x": 1}"#,
+            r#"{"a": {"b": {"c": 1}}, "l": [1, 2], "x": 2}"#,
+        )
+        .unwrap();
+        let mut options = RenderOptions {
+            collapse_unchanged: true,
+            context_depth: 1,
+            ..Default::default()
+        };
+
+        let res = generate_with_options(json.clone(), &options);
+        assert!(res.contains(
+            "b: </span>\n<span class=\"collapsed\" style=\"color:grey\">{\u{2026}1 key unchanged}"
+        ));
+        assert!(!res.contains("[\u{2026}2 elements unchanged]"));
+        assert!(!res.contains("c: "));
+
+        options.context_depth = 0;
+        options.expandable = true;
+        let res = generate_with_options(json, &options);
+        assert!(res.contains("<summary style=\"color:grey\">{\u{2026}1 key unchanged}</summary>"));
+        assert!(
+            res.contains("<summary style=\"color:grey\">[\u{2026}2 elements unchanged]</summary>")
+        );
+        assert!(res.contains("c: "));
+    }
+
+    #[test]
+    fn test_collapse_unchanged_scalars() {
+        let json = crate::json_diff::diff(
+            r#"{"a": 1, "b": "x", "c": null, "d": 1, "e": [1], "f": true}"#,
+            r#"{"a": 1, "b": "x", "c": null, "d": 2, "e": [1], "f": true}"#,
+        )
+        .unwrap();
+        let mut options = RenderOptions {
+            collapse_unchanged: true,
+            ..Default::default()
+        };
+
+        let res = generate_with_options(json.clone(), &options);
+        assert!(res.contains("\u{2026}3 keys unchanged"));
+        assert!(!res.contains("b: "));
+        assert!(res.contains("f: "));
+
+        options.expandable = true;
+        let res = generate_with_options(json, &options);
+        assert!(res.contains("<summary style=\"color:grey\">\u{2026}3 keys unchanged</summary>"));
+        assert!(res.contains("b: "));
+    }
+
+    #[test]
+    fn test_object_key_order() {
+        use crate::json_diff::{diff_with_options, DiffOptions, KeyOrder};
+        let options = DiffOptions {
+            key_order: KeyOrder::First,
+            ..Default::default()
+        };
+        let json = diff_with_options(r#"{"z":1,"b":2,"a":3}"#, r#"{"z":1,"b":5,"a":3}"#, &options)
+            .unwrap();
+        let res = generate(json);
+        let z = res.find("z: ").unwrap();
+        let b = res.find("b: ").unwrap();
+        let a = res.find("a: ").unwrap();
+        assert!(z < b && b < a);
+    }
 }
@@ -1,18 +1,38 @@
-use serde_json::{Result, Value};
-use std::collections::BTreeMap;
+use indexmap::IndexMap;
+use serde_json::{Map, Number, Value};
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
+use std::rc::Rc;
 
+use super::comparator::{Comparator, Comparison};
 use super::edit_distance;
-use edit_distance::{edit_distance, EditType};
+use super::error::{Error, ParseError, Result};
+use super::path::{find_rule, PathPattern};
+use super::similarity::similarity;
+use edit_distance::{edit_distance_linear, edit_distance_with_cost, EditType};
+use unicode_normalization::UnicodeNormalization;
 
 #[derive(Clone, Debug)]
 pub enum JsonV {
     Null(Option<Box<JsonVPair>>),
-    String(String, Option<Box<JsonVPair>>),
+    /// The segments locate the changes within two differing strings, see
+    /// `DiffOptions::string_granularity`
+    String(String, Option<Box<JsonVPair>>, Vec<StringSegment>),
     Bool(bool, Option<Box<JsonVPair>>),
-    Number(f64, Option<Box<JsonVPair>>),
+    /// Numbers equal within a tolerance keep the second number, see
+    /// `DiffOptions::number_tolerances`
+    Number(Number, Option<Box<JsonVPair>>, Option<WithinTolerance>),
     Array(Vec<(usize, JsonV)>, Vec<ArrayDiff>),
-    Object(BTreeMap<String, JsonV>, Vec<ObjectDiff>),
+    /// The values equal in both objects, the differences, and every key of both objects
+    /// in the order of `DiffOptions::key_order`
+    Object(IndexMap<String, JsonV>, Vec<ObjectDiff>, Vec<String>),
+    /// A value matched by an ignore rule, taken from the first document if present there
+    Ignored(Box<JsonV>),
+    /// Values of different types, with the type of the first and of the second
+    TypeChanged(JsonType, JsonType, Box<JsonVPair>),
+    /// Two objects or two arrays equal as structures that a comparator found different,
+    /// see `Comparison::Different`
+    Replaced(Box<JsonVPair>),
 }
 
 impl fmt::Display for JsonV {
@@ -22,55 +42,607 @@ impl fmt::Display for JsonV {
     }
 }
 
+impl JsonV {
+    /// Rebuilds the value of the first document.
+    pub fn first_value(&self) -> Value {
+        side_value(self, true)
+    }
+
+    /// Rebuilds the value of the second document.
+    pub fn second_value(&self) -> Value {
+        side_value(self, false)
+    }
+
+    /// The diff going from the second document to the first.
+    pub fn invert(&self) -> JsonV {
+        let flip = |pair: &JsonVPair| Box::new((pair.1.invert(), pair.0.invert()));
+        match self {
+            JsonV::Null(pair) => JsonV::Null(pair.as_deref().map(flip)),
+            JsonV::Bool(b, pair) => JsonV::Bool(*b, pair.as_deref().map(flip)),
+            JsonV::Number(n, pair, None) => {
+                JsonV::Number(n.clone(), pair.as_deref().map(flip), None)
+            }
+            JsonV::Number(n, _, Some(within)) => JsonV::Number(
+                within.second.clone(),
+                None,
+                Some(WithinTolerance {
+                    second: n.clone(),
+                    delta: -within.delta,
+                }),
+            ),
+            JsonV::String(s, pair, segments) => {
+                let mut inverted: Vec<StringSegment> = segments
+                    .iter()
+                    .map(|x| match x {
+                        StringSegment::Same(s) => StringSegment::Same(s.to_string()),
+                        StringSegment::InFirst(s) => StringSegment::InSecond(s.to_string()),
+                        StringSegment::InSecond(s) => StringSegment::InFirst(s.to_string()),
+                    })
+                    .collect();
+                // Within a changed run the removed text comes before the inserted text
+                for run in inverted.split_mut(|x| matches!(x, StringSegment::Same(_))) {
+                    run.sort_by_key(|x| matches!(x, StringSegment::InSecond(_)));
+                }
+                JsonV::String(s.to_string(), pair.as_deref().map(flip), inverted)
+            }
+            JsonV::TypeChanged(t1, t2, pair) => JsonV::TypeChanged(*t2, *t1, flip(pair)),
+            JsonV::Replaced(pair) => JsonV::Replaced(flip(pair)),
+            JsonV::Ignored(v) => JsonV::Ignored(Box::new(v.invert())),
+            JsonV::Object(similarities, differences, keys) => {
+                let mut inverted: Vec<ObjectDiff> = differences
+                    .iter()
+                    .map(|d| match d {
+                        ObjectDiff::ObjectKeyMissing(k, v) => {
+                            ObjectDiff::ObjectKeyPresent(k.to_string(), v.invert())
+                        }
+                        ObjectDiff::ObjectKeyPresent(k, v) => {
+                            ObjectDiff::ObjectKeyMissing(k.to_string(), v.invert())
+                        }
+                        ObjectDiff::ObjectValueDiff(k, v) => {
+                            ObjectDiff::ObjectValueDiff(k.to_string(), v.invert())
+                        }
+                        ObjectDiff::ObjectKeysReordered(first, second) => {
+                            ObjectDiff::ObjectKeysReordered(second.clone(), first.clone())
+                        }
+                        ObjectDiff::ObjectKeyRenamed(k1, k2, v) => {
+                            ObjectDiff::ObjectKeyRenamed(k2.to_string(), k1.to_string(), v.invert())
+                        }
+                    })
+                    .collect();
+                // Keep the order `diff_rec` produces, keys only in the first come first
+                inverted.sort_by_key(|d| match d {
+                    ObjectDiff::ObjectKeyPresent(_, _) => 0,
+                    ObjectDiff::ObjectKeyMissing(_, _) => 1,
+                    _ => 2,
+                });
+                JsonV::Object(
+                    similarities
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), v.invert()))
+                        .collect(),
+                    inverted,
+                    keys.clone(),
+                )
+            }
+            JsonV::Array(same, differences) => JsonV::Array(
+                same.iter().map(|(i, v)| (*i, v.invert())).collect(),
+                differences
+                    .iter()
+                    .map(|d| match d {
+                        ArrayDiff::ArrayValueInSecond(i, v) => {
+                            ArrayDiff::ArrayValueInFirst(*i, v.invert())
+                        }
+                        ArrayDiff::ArrayValueInFirst(i, v) => {
+                            ArrayDiff::ArrayValueInSecond(*i, v.invert())
+                        }
+                        ArrayDiff::ArrayValueDiff(i, v) => {
+                            ArrayDiff::ArrayValueDiff(*i, v.invert())
+                        }
+                        ArrayDiff::ArrayValueMovedAway(i, to, v) => {
+                            ArrayDiff::ArrayValueMoved(*i, *to, v.invert())
+                        }
+                        ArrayDiff::ArrayValueMoved(i, from, v) => {
+                            ArrayDiff::ArrayValueMovedAway(*i, *from, v.invert())
+                        }
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+fn side_value(j: &JsonV, first: bool) -> Value {
+    let pick = |pair: &JsonVPair| side_value(if first { &pair.0 } else { &pair.1 }, first);
+    match j {
+        JsonV::Null(None) => Value::Null,
+        JsonV::Bool(b, None) => Value::Bool(*b),
+        JsonV::Number(n, None, None) => Value::Number(n.clone()),
+        JsonV::Number(n, None, Some(within)) => {
+            Value::Number(if first { n } else { &within.second }.clone())
+        }
+        JsonV::String(s, None, _) => Value::String(s.to_string()),
+        JsonV::Null(Some(pair))
+        | JsonV::Bool(_, Some(pair))
+        | JsonV::Number(_, Some(pair), _)
+        | JsonV::String(_, Some(pair), _)
+        | JsonV::TypeChanged(_, _, pair)
+        | JsonV::Replaced(pair) => pick(pair),
+        JsonV::Ignored(v) => side_value(v, first),
+        JsonV::Object(similarities, differences, _) => {
+            let mut map: Map<String, Value> = similarities
+                .iter()
+                .map(|(k, v)| (k.to_string(), side_value(v, first)))
+                .collect();
+            for d in differences {
+                match d {
+                    ObjectDiff::ObjectKeyPresent(k, v) if first => {
+                        map.insert(k.to_string(), side_value(v, first));
+                    }
+                    ObjectDiff::ObjectKeyMissing(k, v) if !first => {
+                        map.insert(k.to_string(), side_value(v, first));
+                    }
+                    ObjectDiff::ObjectValueDiff(k, v) => {
+                        map.insert(k.to_string(), side_value(v, first));
+                    }
+                    ObjectDiff::ObjectKeyRenamed(k1, k2, v) => {
+                        let k = if first { k1 } else { k2 };
+                        map.insert(k.to_string(), side_value(v, first));
+                    }
+                    _ => (),
+                }
+            }
+            Value::Object(map)
+        }
+        JsonV::Array(same, differences) => Value::Array(
+            array_entries(same, differences)
+                .into_iter()
+                .filter_map(|entry| match entry {
+                    ArrayEntry::Same(v) => Some(side_value(v, first)),
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueInFirst(_, v)) if first => {
+                        Some(side_value(v, first))
+                    }
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueInSecond(_, v)) if !first => {
+                        Some(side_value(v, first))
+                    }
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueMovedAway(_, _, v)) if first => {
+                        Some(side_value(v, first))
+                    }
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueMoved(_, _, v)) if !first => {
+                        Some(side_value(v, first))
+                    }
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueDiff(_, v)) => Some(side_value(v, first)),
+                    _ => None,
+                })
+                .collect(),
+        ),
+    }
+}
+
 pub type JsonVPair = (JsonV, JsonV);
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JsonType {
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl JsonType {
+    pub fn of(v: &Value) -> JsonType {
+        match v {
+            Value::Null => JsonType::Null,
+            Value::Bool(_) => JsonType::Bool,
+            Value::Number(_) => JsonType::Number,
+            Value::String(_) => JsonType::String,
+            Value::Array(_) => JsonType::Array,
+            Value::Object(_) => JsonType::Object,
+        }
+    }
+}
+
+impl fmt::Display for JsonType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            JsonType::Null => "null",
+            JsonType::Bool => "boolean",
+            JsonType::Number => "number",
+            JsonType::String => "string",
+            JsonType::Array => "array",
+            JsonType::Object => "object",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StringSegment {
+    Same(String),
+    InFirst(String),
+    InSecond(String),
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Clone, Debug)]
 pub enum ArrayDiff {
     ArrayValueInSecond(usize, JsonV),
     ArrayValueInFirst(usize, JsonV),
+    ArrayValueDiff(usize, JsonV),
+    /// An element of the first array moved to the given index of the second, with the
+    /// diff between the two, see `DiffOptions::move_threshold` and
+    /// `DiffOptions::identity_keys`
+    ArrayValueMovedAway(usize, usize, JsonV),
+    /// An element of the second array moved from the given index of the first, with the
+    /// diff between the two
+    ArrayValueMoved(usize, usize, JsonV),
+}
+
+impl ArrayDiff {
+    /// The position of the element among the elements of both arrays.
+    pub fn position(&self) -> usize {
+        match self {
+            ArrayDiff::ArrayValueInSecond(i, _) => *i,
+            ArrayDiff::ArrayValueInFirst(i, _) => *i,
+            ArrayDiff::ArrayValueDiff(i, _) => *i,
+            ArrayDiff::ArrayValueMovedAway(i, _, _) => *i,
+            ArrayDiff::ArrayValueMoved(i, _, _) => *i,
+        }
+    }
+}
+
+pub enum ArrayEntry<'a> {
+    Same(&'a JsonV),
+    Diff(&'a ArrayDiff),
+}
+
+/// The elements of an array diff in the order they appear in the documents.
+pub fn array_entries<'a>(
+    same: &'a [(usize, JsonV)],
+    differences: &'a [ArrayDiff],
+) -> Vec<ArrayEntry<'a>> {
+    let mut entries: Vec<(usize, ArrayEntry)> = same
+        .iter()
+        .map(|(i, v)| (*i, ArrayEntry::Same(v)))
+        .chain(
+            differences
+                .iter()
+                .map(|d| (d.position(), ArrayEntry::Diff(d))),
+        )
+        .collect();
+    entries.sort_by_key(|(i, _)| *i);
+
+    entries.into_iter().map(|(_, entry)| entry).collect()
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Clone, Debug)]
 pub enum ObjectDiff {
     ObjectKeyMissing(String, JsonV),
     ObjectKeyPresent(String, JsonV),
     ObjectValueDiff(String, JsonV),
+    /// The keys present in both objects, in the order of the first and of the second
+    ObjectKeysReordered(Vec<String>, Vec<String>),
+    /// A key of the first object renamed to a key of the second, with the diff between
+    /// their values, see `DiffOptions::rename_threshold`
+    ObjectKeyRenamed(String, String, JsonV),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+    /// Keys identifying the elements of arrays of objects. Elements with the same
+    /// identity are paired and diffed wherever they are in the arrays, and reported as
+    /// moved if they changed order.
+    pub identity_keys: Vec<(PathPattern, String)>,
+    /// Numbers within the tolerance of the first matching rule are reported as equal.
+    pub number_tolerances: Vec<(PathPattern, Tolerance)>,
+    /// Strings equivalent under the first matching rule are reported as equal. The
+    /// result still shows the strings as they are.
+    pub string_equivalences: Vec<(PathPattern, StringEquivalence)>,
+    /// A key only in one object is not reported if the first matching rule makes its
+    /// value equivalent to the key being missing.
+    pub missing_equivalences: Vec<(PathPattern, MissingEquivalence)>,
+    /// Consulted in order before the built-in comparison, the first matching one that is
+    /// not `Comparison::Undecided` decides.
+    pub comparators: Vec<(PathPattern, Rc<dyn Comparator>)>,
+    pub key_order: KeyOrder,
+    /// Report objects whose common keys appear in a different order.
+    pub report_key_reordering: bool,
+    /// Subtrees that are neither compared nor counted as differences.
+    pub ignore: Vec<PathPattern>,
+    /// Arrays compared as multisets, where the position of elements does not matter.
+    pub unordered_arrays: Vec<PathPattern>,
+    /// Report elements deleted from one position of an array and inserted at another as
+    /// moved, if their similarity is at least this. 1.0 only detects identical elements.
+    pub move_threshold: Option<f64>,
+    /// Report a key only in the first object and a key only in the second as renamed, if
+    /// the similarity of their values is at least this.
+    pub rename_threshold: Option<f64>,
+    /// Split differing strings into segments of this granularity.
+    pub string_granularity: Option<Granularity>,
+    /// The maximum length of each input in bytes.
+    pub max_input_len: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Granularity {
+    Chars,
+    /// Words and the whitespace between them
+    Words,
+}
+
+/// The order in which the keys of objects are listed in the result.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum KeyOrder {
+    #[default]
+    Sorted,
+    /// The order of the first document, followed by keys only in the second
+    First,
+    /// The order of the second document, followed by keys only in the first
+    Second,
+}
+
+impl DiffOptions {
+    pub(crate) fn identity_key(&self, path: &[String]) -> Option<&str> {
+        find_rule(&self.identity_keys, path).map(|k| k.as_str())
+    }
+
+    pub(crate) fn is_ignored(&self, path: &[String]) -> bool {
+        self.ignore.iter().any(|pattern| pattern.matches(path))
+    }
+
+    pub(crate) fn is_unordered(&self, path: &[String]) -> bool {
+        self.unordered_arrays
+            .iter()
+            .any(|pattern| pattern.matches(path))
+    }
+
+    fn numbers_equal(&self, n1: &Number, n2: &Number, path: &[String]) -> bool {
+        numbers_equal(n1, n2)
+            || find_rule(&self.number_tolerances, path).is_some_and(|t| t.contains(n1, n2))
+    }
+
+    pub(crate) fn is_missing_equivalent(&self, value: &Value, path: &[String]) -> bool {
+        find_rule(&self.missing_equivalences, path).is_some_and(|e| e.matches(value))
+    }
+
+    fn comparison(&self, a: &Value, b: &Value, path: &[String]) -> Comparison {
+        self.comparators
+            .iter()
+            .filter(|(pattern, _)| pattern.matches(path))
+            .map(|(_, comparator)| comparator.compare(a, b))
+            .find(|c| *c != Comparison::Undecided)
+            .unwrap_or(Comparison::Structural)
+    }
+
+    fn strings_equal(&self, s1: &str, s2: &str, path: &[String]) -> bool {
+        s1 == s2
+            || find_rule(&self.string_equivalences, path)
+                .is_some_and(|e| e.normalize(s1) == e.normalize(s2))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tolerance {
+    pub absolute: f64,
+    /// Relative to the larger of the two magnitudes
+    pub relative: f64,
+}
+
+impl Tolerance {
+    pub fn contains(&self, n1: &Number, n2: &Number) -> bool {
+        match (n1.as_f64(), n2.as_f64()) {
+            (Some(a), Some(b)) => {
+                let delta = (a - b).abs();
+                delta <= self.absolute || delta <= self.relative * a.abs().max(b.abs())
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A number of the second document within tolerance of the number of the first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WithinTolerance {
+    pub second: Number,
+    /// The second number minus the first
+    pub delta: f64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StringEquivalence {
+    pub ignore_case: bool,
+    /// Ignore leading and trailing whitespace
+    pub trim: bool,
+    /// Treat every run of whitespace as a single space
+    pub collapse_whitespace: bool,
+    pub normalization: Option<Normalization>,
+    /// Treat `\r\n` and `\r` as `\n`
+    pub normalize_line_endings: bool,
+}
+
+/// Unicode normalization forms.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Normalization {
+    Nfc,
+    Nfkc,
+}
+
+impl StringEquivalence {
+    /// The form in which equivalent strings are equal.
+    pub fn normalize(&self, s: &str) -> String {
+        let mut normalized = s.to_string();
+        if self.normalize_line_endings {
+            normalized = normalized.replace("\r\n", "\n").replace('\r', "\n");
+        }
+        normalized = match self.normalization {
+            Some(Normalization::Nfc) => normalized.nfc().collect(),
+            Some(Normalization::Nfkc) => normalized.nfkc().collect(),
+            None => normalized,
+        };
+        if self.trim {
+            normalized = normalized.trim().to_string();
+        }
+        if self.collapse_whitespace {
+            let mut collapsed = String::new();
+            for c in normalized.chars() {
+                if !c.is_whitespace() {
+                    collapsed.push(c);
+                } else if !collapsed.ends_with(' ') {
+                    collapsed.push(' ');
+                }
+            }
+            normalized = collapsed;
+        }
+        if self.ignore_case {
+            normalized = normalized.to_lowercase();
+        }
+
+        normalized
+    }
+}
+
+/// The values that are equivalent to a missing key.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MissingEquivalence {
+    /// Empty arrays and objects as well as null
+    pub empty_containers: bool,
+}
+
+impl MissingEquivalence {
+    pub fn matches(&self, value: &Value) -> bool {
+        match value {
+            Value::Null => true,
+            Value::Array(a) => self.empty_containers && a.is_empty(),
+            Value::Object(o) => self.empty_containers && o.is_empty(),
+            _ => false,
+        }
+    }
 }
 
 pub fn diff(a: &str, b: &str) -> Result<JsonV> {
-    let a_as_json: Value = serde_json::from_str(a)?;
-    let b_as_json: Value = serde_json::from_str(b)?;
+    diff_with_options(a, b, &DiffOptions::default())
+}
 
-    let json = diff_rec(&a_as_json, &b_as_json);
+pub fn diff_with_options(a: &str, b: &str, options: &DiffOptions) -> Result<JsonV> {
+    if let Some(max_input_len) = options.max_input_len {
+        let longest = a.len().max(b.len());
+        if longest > max_input_len {
+            return Err(Error::Limit(format!(
+                "input of {} bytes is longer than the maximum of {} bytes",
+                longest, max_input_len
+            )));
+        }
+    }
+    let a_as_json: Value =
+        serde_json::from_str(a).map_err(|e| Error::LeftParse(ParseError::new(&e, a)))?;
+    let b_as_json: Value =
+        serde_json::from_str(b).map_err(|e| Error::RightParse(ParseError::new(&e, b)))?;
+
+    Ok(diff_values(&a_as_json, &b_as_json, options))
+}
 
-    Ok(json)
+/// Diffs two already parsed documents.
+pub fn diff_values(a: &Value, b: &Value, options: &DiffOptions) -> JsonV {
+    diff_rec(a, b, options, &[])
 }
 
-fn diff_rec(arg1: &Value, arg2: &Value) -> JsonV {
+pub(crate) fn child_path(path: &[String], token: &str) -> Vec<String> {
+    let mut child = path.to_vec();
+    child.push(token.to_string());
+    child
+}
+
+pub(crate) fn diff_rec(
+    arg1: &Value,
+    arg2: &Value,
+    options: &DiffOptions,
+    path: &[String],
+) -> JsonV {
+    if options.is_ignored(path) {
+        return JsonV::Ignored(Box::new(convert(arg1, options)));
+    }
+    match options.comparison(arg1, arg2, path) {
+        Comparison::Equal => convert(arg1, options),
+        Comparison::Different if is_same_container_type(arg1, arg2) => {
+            let json = diff_structures(arg1, arg2, options, path);
+            if has_differences(&json) {
+                json
+            } else {
+                let pair = (convert(arg1, options), convert(arg2, options));
+                JsonV::Replaced(Box::new(pair))
+            }
+        }
+        Comparison::Different => replaced(arg1, arg2, options),
+        Comparison::Structural | Comparison::Undecided => {
+            diff_structures(arg1, arg2, options, path)
+        }
+    }
+}
+
+fn diff_structures(arg1: &Value, arg2: &Value, options: &DiffOptions, path: &[String]) -> JsonV {
     match (arg1, arg2) {
         // Check keys first then values
         (Value::Object(a_obj), Value::Object(b_obj)) => {
-            let a_keys: Vec<_> = a_obj.keys().map(|x| x.to_string()).collect::<Vec<String>>();
-            let b_keys: Vec<_> = b_obj.keys().map(|x| x.to_string()).collect::<Vec<String>>();
+            let ordered = ordered_keys(a_obj, b_obj, options.key_order);
+            let (ignored_keys, keys): (Vec<String>, Vec<String>) = ordered
+                .iter()
+                .cloned()
+                .partition(|x| options.is_ignored(&child_path(path, x)));
+            // Keys only in one object whose value is as good as missing
+            let (equivalent_keys, keys): (Vec<String>, Vec<String>) =
+                keys.into_iter()
+                    .partition(|x| match (a_obj.get(x), b_obj.get(x)) {
+                        (Some(value), None) | (None, Some(value)) => {
+                            options.is_missing_equivalent(value, &child_path(path, x))
+                        }
+                        _ => false,
+                    });
             // Find fields not in the other and vice versa
-            let fields_in_a_not_b = a_intersection_complement_b(a_keys.clone(), b_keys.clone());
-            let fields_in_b_not_a = a_intersection_complement_b(b_keys.clone(), a_keys.clone());
+            let fields_in_a_not_b: Vec<&String> =
+                keys.iter().filter(|x| !b_obj.contains_key(*x)).collect();
+            let fields_in_b_not_a: Vec<&String> =
+                keys.iter().filter(|x| !a_obj.contains_key(*x)).collect();
 
             // Find fields where the values differ in the two structures
 
             let mut differences: Vec<ObjectDiff> = Vec::new();
-            let mut similarities: BTreeMap<String, JsonV> = BTreeMap::new();
-            for key in union(a_keys.clone(), b_keys.clone()) {
-                if let (Some(a_value), Some(b_value)) = (a_obj.get(&key), b_obj.get(&key)) {
-                    let json_element = diff_rec(a_value, b_value);
+            let mut similarities: IndexMap<String, JsonV> = IndexMap::new();
+            for key in ignored_keys {
+                if let Some(value) = a_obj.get(&key).or_else(|| b_obj.get(&key)) {
+                    similarities.insert(key, JsonV::Ignored(Box::new(convert(value, options))));
+                }
+            }
+            for key in equivalent_keys {
+                if let Some(value) = a_obj.get(&key).or_else(|| b_obj.get(&key)) {
+                    similarities.insert(key, convert(value, options));
+                }
+            }
+            for key in keys.iter() {
+                if let (Some(a_value), Some(b_value)) = (a_obj.get(key), b_obj.get(key)) {
+                    let json_element = diff_rec(a_value, b_value, options, &child_path(path, key));
                     if has_differences(&json_element) {
-                        differences.push(ObjectDiff::ObjectValueDiff(key, json_element));
+                        differences
+                            .push(ObjectDiff::ObjectValueDiff(key.to_string(), json_element));
                     } else {
                         similarities.insert(key.to_string(), json_element);
                     }
                 }
             }
 
+            if options.report_key_reordering {
+                let common_keys = |obj: &Map<String, Value>, other: &Map<String, Value>| {
+                    obj.keys()
+                        .filter(|x| other.contains_key(*x))
+                        .cloned()
+                        .collect::<Vec<String>>()
+                };
+                let (a_common, b_common) = (common_keys(a_obj, b_obj), common_keys(b_obj, a_obj));
+                if a_common != b_common {
+                    differences.push(ObjectDiff::ObjectKeysReordered(a_common, b_common));
+                }
+            }
 
             let all_differences = if fields_in_a_not_b.is_empty()
                 && fields_in_b_not_a.is_empty()
@@ -80,150 +652,570 @@ fn diff_rec(arg1: &Value, arg2: &Value) -> JsonV {
                 Vec::new()
             } else {
                 // Not the same
+                let renamed = match options.rename_threshold {
+                    Some(threshold) => {
+                        let first: Vec<(&Value, Vec<String>)> = fields_in_a_not_b
+                            .iter()
+                            .map(|x| (&a_obj[*x], child_path(path, x)))
+                            .collect();
+                        let second: Vec<&Value> =
+                            fields_in_b_not_a.iter().map(|x| &b_obj[*x]).collect();
+                        pair_similar(&first, &second, threshold, options)
+                    }
+                    None => Vec::new(),
+                };
                 let entries_present_in_a: Vec<ObjectDiff> = fields_in_a_not_b
                     .iter()
-                    .map(|x| {
-                        ObjectDiff::ObjectKeyPresent(x.to_string(), convert(a_obj.get(x).unwrap()))
+                    .enumerate()
+                    .filter(|(i, _)| !renamed.iter().any(|(x, _, _)| x == i))
+                    .map(|(_, x)| {
+                        ObjectDiff::ObjectKeyPresent(x.to_string(), convert(&a_obj[*x], options))
                     })
                     .collect();
                 let entries_missing_in_a: Vec<ObjectDiff> = fields_in_b_not_a
                     .iter()
-                    .map(|x| {
-                        ObjectDiff::ObjectKeyMissing(x.to_string(), convert(b_obj.get(x).unwrap()))
+                    .enumerate()
+                    .filter(|(j, _)| !renamed.iter().any(|(_, x, _)| x == j))
+                    .map(|(_, x)| {
+                        ObjectDiff::ObjectKeyMissing(x.to_string(), convert(&b_obj[*x], options))
+                    })
+                    .collect();
+                let entries_renamed: Vec<ObjectDiff> = renamed
+                    .into_iter()
+                    .map(|(i, j, json_element)| {
+                        ObjectDiff::ObjectKeyRenamed(
+                            fields_in_a_not_b[i].to_string(),
+                            fields_in_b_not_a[j].to_string(),
+                            json_element,
+                        )
                     })
                     .collect();
 
-                [entries_present_in_a, entries_missing_in_a, differences].concat()
+                [
+                    entries_present_in_a,
+                    entries_missing_in_a,
+                    entries_renamed,
+                    differences,
+                ]
+                .concat()
             };
-            JsonV::Object(similarities, all_differences)
+            JsonV::Object(similarities, all_differences, ordered)
         }
         // Check equal number of elements and element equality
         (Value::Array(arr1), Value::Array(arr2)) => {
-            let arr1_elements_serialized: Vec<String> =
-                arr1.iter().map(|x| x.to_string()).collect();
-            let arr2_elements_serialized: Vec<String> =
-                arr2.iter().map(|x| x.to_string()).collect();
+            let identity_key = options.identity_key(path);
+            let arr1_elements = array_elements(arr1, path, identity_key);
+            let arr2_elements = array_elements(arr2, path, identity_key);
+            if options.is_unordered(path) {
+                return diff_unordered(&arr1_elements, &arr2_elements, options);
+            }
 
-            let edit_types = edit_distance(arr1_elements_serialized, arr2_elements_serialized);
+            let edit_types = edit_distance_with_cost(
+                arr1_elements.iter().collect(),
+                arr2_elements.iter().collect(),
+                |a, b| substitution_cost(a, b, options),
+            );
             let mut same: Vec<(usize, JsonV)> = Vec::new();
             let mut diffs: Vec<ArrayDiff> = Vec::new();
+            // The positions and indices of the elements only in either array
+            let (mut deleted, mut inserted) = (Vec::new(), Vec::new());
             for (i, edit_type) in edit_types.iter().enumerate() {
                 match edit_type {
                     EditType::Insert(index) => {
-                        diffs.push(ArrayDiff::ArrayValueInSecond(i, convert(&arr2[*index])))
+                        inserted.push((i, *index));
+                        diffs.push(ArrayDiff::ArrayValueInSecond(
+                            i,
+                            convert(&arr2[*index], options),
+                        ))
                     }
                     EditType::Delete(index) => {
-                        diffs.push(ArrayDiff::ArrayValueInFirst(i, convert(&arr1[*index])))
+                        deleted.push((i, *index));
+                        diffs.push(ArrayDiff::ArrayValueInFirst(
+                            i,
+                            convert(&arr1[*index], options),
+                        ))
                     }
-                    EditType::Substitute(index, _, is_same) if *is_same => {
-                        same.push((i, convert(&arr1[*index])))
+                    EditType::Substitute(index_arg1, index_arg2, is_same)
+                        if *is_same
+                            || (is_same_container_type(&arr1[*index_arg1], &arr2[*index_arg2])
+                                && !arr1_elements[*index_arg1].keyed
+                                && !arr2_elements[*index_arg2].keyed) =>
+                    {
+                        // Elements with the same identity may still differ in other fields,
+                        // and differing ones may turn out equal once ignore rules apply
+                        let json_element = diff_rec(
+                            &arr1[*index_arg1],
+                            &arr2[*index_arg2],
+                            options,
+                            &arr1_elements[*index_arg1].path,
+                        );
+                        if has_differences(&json_element) {
+                            diffs.push(ArrayDiff::ArrayValueDiff(i, json_element));
+                        } else {
+                            same.push((i, json_element));
+                        }
                     }
                     EditType::Substitute(index_arg1, index_arg2, _) => {
+                        inserted.push((i, *index_arg2));
+                        deleted.push((i, *index_arg1));
                         diffs.push(ArrayDiff::ArrayValueInSecond(
                             i,
-                            convert(&arr2[*index_arg2]),
+                            convert(&arr2[*index_arg2], options),
+                        ));
+                        diffs.push(ArrayDiff::ArrayValueInFirst(
+                            i,
+                            convert(&arr1[*index_arg1], options),
                         ));
-                        diffs.push(ArrayDiff::ArrayValueInFirst(i, convert(&arr1[*index_arg1])));
                     }
                     EditType::Unknown => (),
                 }
             }
+            // Records with the same identity that changed order are moved
+            let keyed_moves = pair_keyed(&deleted, &inserted, &arr1_elements, &arr2_elements);
+            for (d, n) in &keyed_moves {
+                let (from, to) = (deleted[*d].1, inserted[*n].1);
+                let json_element =
+                    diff_rec(&arr1[from], &arr2[to], options, &arr1_elements[from].path);
+                mark_moved(&mut diffs, deleted[*d], inserted[*n], json_element);
+            }
+            let unmoved =
+                |entries: Vec<(usize, usize)>, moved: Vec<usize>| -> Vec<(usize, usize)> {
+                    entries
+                        .into_iter()
+                        .enumerate()
+                        .filter(|(x, _)| !moved.contains(x))
+                        .map(|(_, entry)| entry)
+                        .collect()
+                };
+            let deleted = unmoved(deleted, keyed_moves.iter().map(|(d, _)| *d).collect());
+            let inserted = unmoved(inserted, keyed_moves.iter().map(|(_, n)| *n).collect());
+            if let Some(threshold) = options.move_threshold {
+                let elements = (arr1_elements.as_slice(), arr2_elements.as_slice());
+                detect_moves(
+                    &mut diffs, &deleted, &inserted, elements, threshold, options,
+                );
+            }
 
             JsonV::Array(same, diffs)
         }
+        (Value::String(s1), Value::String(s2)) if options.strings_equal(s1, s2, path) => {
+            JsonV::String(s1.to_string(), None, Vec::new())
+        }
+        (Value::Number(n1), Value::Number(n2)) if options.numbers_equal(n1, n2, path) => {
+            let within = (!numbers_equal(n1, n2)).then(|| WithinTolerance {
+                second: n2.clone(),
+                delta: n2.as_f64().unwrap_or(0.0) - n1.as_f64().unwrap_or(0.0),
+            });
+            JsonV::Number(n1.clone(), None, within)
+        }
+        (Value::Bool(b1), Value::Bool(b2)) if b1 == b2 => JsonV::Bool(*b1, None),
+        (Value::Null, Value::Null) => JsonV::Null(None),
+        _ => replaced(arg1, arg2, options),
+    }
+}
+
+/// The diff of two values that are not equal, unless both are objects or arrays.
+fn replaced(arg1: &Value, arg2: &Value, options: &DiffOptions) -> JsonV {
+    let pair = Box::new((convert(arg1, options), convert(arg2, options)));
+    match (arg1, arg2) {
         (Value::String(s1), Value::String(s2)) => {
-            if s1 == s2 {
-                JsonV::String(s1.to_string(), None)
-            } else {
-                JsonV::String("".to_string(), Some(Box::new((convert(arg1), convert(arg2)))))
+            let segments = options
+                .string_granularity
+                .map_or_else(Vec::new, |g| string_segments(s1, s2, g));
+            JsonV::String("".to_string(), Some(pair), segments)
+        }
+        (Value::Number(_), Value::Number(_)) => JsonV::Number(0.into(), Some(pair), None),
+        (Value::Bool(_), Value::Bool(_)) => JsonV::Bool(false, Some(pair)),
+        (Value::Null, Value::Null) => JsonV::Null(Some(pair)),
+        _ => JsonV::TypeChanged(JsonType::of(arg1), JsonType::of(arg2), pair),
+    }
+}
+
+/// An array element prepared for alignment by `edit_distance_with_cost`.
+struct ArrayElement<'a> {
+    /// Objects carrying the identity key are aligned on that key alone, everything
+    /// else on its serialized value.
+    identity: String,
+    keyed: bool,
+    value: &'a Value,
+    path: Vec<String>,
+}
+
+fn array_elements<'a>(
+    arr: &'a [Value],
+    path: &[String],
+    identity_key: Option<&str>,
+) -> Vec<ArrayElement<'a>> {
+    arr.iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let (identity, keyed) = match (v, identity_key) {
+                (Value::Object(o), Some(key)) if o.contains_key(key) => {
+                    (format!("#{}={}", key, o[key]), true)
+                }
+                _ => (v.to_string(), false),
+            };
+            ArrayElement {
+                identity,
+                keyed,
+                value: v,
+                path: child_path(path, &i.to_string()),
             }
+        })
+        .collect()
+}
+
+/// Pairs the deleted and inserted records with the same identity key, in order, as
+/// indices into `deleted` and `inserted`.
+fn pair_keyed(
+    deleted: &[(usize, usize)],
+    inserted: &[(usize, usize)],
+    arr1_elements: &[ArrayElement],
+    arr2_elements: &[ArrayElement],
+) -> Vec<(usize, usize)> {
+    let mut keyed2: HashMap<&str, VecDeque<usize>> = HashMap::new();
+    for (n, (_, j)) in inserted.iter().enumerate() {
+        if arr2_elements[*j].keyed {
+            keyed2
+                .entry(arr2_elements[*j].identity.as_str())
+                .or_default()
+                .push_back(n);
         }
-        (Value::Number(n1), Value::Number(n2)) => {
-            if cmp_option(n1.as_f64(), n2.as_f64()) {
-                JsonV::Number(n1.as_f64().unwrap(), None)
-            } else {
-                JsonV::Number(0.0, Some(Box::new((convert(arg1), convert(arg2)))))
+    }
+    deleted
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, i))| arr1_elements[*i].keyed)
+        .filter_map(|(d, (_, i))| {
+            let n = keyed2
+                .get_mut(arr1_elements[*i].identity.as_str())?
+                .pop_front()?;
+            Some((d, n))
+        })
+        .collect()
+}
+
+/// Elements with the same identity or found equal by a comparator, numbers within
+/// tolerance and equivalent strings cost nothing to substitute. Two objects or two
+/// arrays are cheaper to substitute than to delete and insert, so that they get paired
+/// and diffed recursively, unless they are records with different identity keys.
+fn substitution_cost(a: &ArrayElement, b: &ArrayElement, options: &DiffOptions) -> usize {
+    match (a.value, b.value) {
+        _ if a.identity == b.identity => 0,
+        _ if options.comparison(a.value, b.value, &a.path) == Comparison::Equal => 0,
+        (Value::Number(n1), Value::Number(n2)) if options.numbers_equal(n1, n2, &a.path) => 0,
+        (Value::String(s1), Value::String(s2)) if options.strings_equal(s1, s2, &a.path) => 0,
+        _ if is_same_container_type(a.value, b.value) && !a.keyed && !b.keyed => 1,
+        _ => 2,
+    }
+}
+
+/// Pairs deleted and inserted elements, most similar first, and reports them as moved.
+fn detect_moves(
+    diffs: &mut [ArrayDiff],
+    deleted: &[(usize, usize)],
+    inserted: &[(usize, usize)],
+    (arr1_elements, arr2_elements): (&[ArrayElement], &[ArrayElement]),
+    threshold: f64,
+    options: &DiffOptions,
+) {
+    let first: Vec<(&Value, Vec<String>)> = deleted
+        .iter()
+        .map(|(_, i)| (arr1_elements[*i].value, arr1_elements[*i].path.clone()))
+        .collect();
+    let second: Vec<&Value> = inserted
+        .iter()
+        .map(|(_, j)| arr2_elements[*j].value)
+        .collect();
+    for (d, n, json_element) in pair_similar(&first, &second, threshold, options) {
+        mark_moved(diffs, deleted[d], inserted[n], json_element);
+    }
+}
+
+/// Replaces the element deleted and the one inserted, given as their position and
+/// index, by a move with the diff between them.
+fn mark_moved(
+    diffs: &mut [ArrayDiff],
+    (deleted_position, from): (usize, usize),
+    (inserted_position, to): (usize, usize),
+    json_element: JsonV,
+) {
+    for diff in diffs.iter_mut() {
+        match diff {
+            ArrayDiff::ArrayValueInFirst(p, _) if *p == deleted_position => {
+                *diff = ArrayDiff::ArrayValueMovedAway(*p, to, json_element.clone());
+            }
+            ArrayDiff::ArrayValueInSecond(p, _) if *p == inserted_position => {
+                *diff = ArrayDiff::ArrayValueMoved(*p, from, json_element.clone());
             }
+            _ => (),
         }
-        (Value::Bool(b1), Value::Bool(b2)) => {
-            if b1 == b2 {
-                JsonV::Bool(*b1, None)
-            } else {
-                JsonV::Bool(
-                    false,
-                    Some(Box::new((JsonV::Bool(*b1, None), JsonV::Bool(*b2, None)))),
-                )
+    }
+}
+
+/// Pairs values of the first list, at the given paths, with values of the second whose
+/// similarity is at least the threshold, most similar first. Returns the indices of the
+/// paired values and their diff.
+fn pair_similar(
+    first: &[(&Value, Vec<String>)],
+    second: &[&Value],
+    threshold: f64,
+    options: &DiffOptions,
+) -> Vec<(usize, usize, JsonV)> {
+    let mut candidates: Vec<(f64, usize, usize, JsonV)> = Vec::new();
+    for (i, (a, path)) in first.iter().enumerate() {
+        for (j, b) in second.iter().enumerate() {
+            if *a == *b || (threshold < 1.0 && is_same_container_type(a, b)) {
+                let json_element = diff_rec(a, b, options, path);
+                let score = similarity(&json_element);
+                if score >= threshold {
+                    candidates.push((score, i, j, json_element));
+                }
             }
         }
-        (Value::Null, Value::Null) => JsonV::Null(None),
-        _ => JsonV::Null(Some(Box::new((convert(arg1), convert(arg2))))),
     }
+    candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    let mut paired_first = vec![false; first.len()];
+    let mut paired_second = vec![false; second.len()];
+    let mut pairs = Vec::new();
+    for (_, i, j, json_element) in candidates {
+        if !paired_first[i] && !paired_second[j] {
+            paired_first[i] = true;
+            paired_second[j] = true;
+            pairs.push((i, j, json_element));
+        }
+    }
+
+    pairs
 }
 
-fn cmp_option<T: std::string::ToString>(a: Option<T>, b: Option<T>) -> bool {
-    match (a, b) {
-        (Some(a), Some(b)) => {
-            a.to_string() == b.to_string()
+/// Compares arrays as multisets. Equal elements are paired first regardless of their
+/// position, then the remaining ones by similarity. Elements are listed in the order of
+/// the first array, followed by the ones only in the second.
+fn diff_unordered(
+    arr1_elements: &[ArrayElement],
+    arr2_elements: &[ArrayElement],
+    options: &DiffOptions,
+) -> JsonV {
+    let mut pairs: Vec<Option<usize>> = vec![None; arr1_elements.len()];
+    let mut paired_in_second = vec![false; arr2_elements.len()];
+    for (i, a) in arr1_elements.iter().enumerate() {
+        let equal = (0..arr2_elements.len()).find(|j| {
+            !paired_in_second[*j] && substitution_cost(a, &arr2_elements[*j], options) == 0
+        });
+        if let Some(j) = equal {
+            pairs[i] = Some(j);
+            paired_in_second[j] = true;
+        }
+    }
+
+    let mut candidates: Vec<(f64, usize, usize, JsonV)> = Vec::new();
+    for (i, a) in arr1_elements
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| pairs[*i].is_none())
+    {
+        for (j, b) in arr2_elements
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| !paired_in_second[*j])
+        {
+            if substitution_cost(a, b, options) == 1 {
+                let json_element = diff_rec(a.value, b.value, options, &a.path);
+                candidates.push((similarity(&json_element), i, j, json_element));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    let mut paired_diffs: Vec<Option<JsonV>> = vec![None; arr1_elements.len()];
+    for (score, i, j, json_element) in candidates {
+        if score > 0.0 && pairs[i].is_none() && !paired_in_second[j] {
+            pairs[i] = Some(j);
+            paired_in_second[j] = true;
+            paired_diffs[i] = Some(json_element);
         }
-        (None, None) => false,
-        _ => false,
     }
+
+    let mut same: Vec<(usize, JsonV)> = Vec::new();
+    let mut diffs: Vec<ArrayDiff> = Vec::new();
+    for (i, a) in arr1_elements.iter().enumerate() {
+        match (pairs[i], paired_diffs[i].take()) {
+            (Some(_), Some(json_element)) => diffs.push(ArrayDiff::ArrayValueDiff(i, json_element)),
+            (Some(j), None) => {
+                let json_element = diff_rec(a.value, arr2_elements[j].value, options, &a.path);
+                if has_differences(&json_element) {
+                    diffs.push(ArrayDiff::ArrayValueDiff(i, json_element));
+                } else {
+                    same.push((i, json_element));
+                }
+            }
+            (None, _) => diffs.push(ArrayDiff::ArrayValueInFirst(i, convert(a.value, options))),
+        }
+    }
+    let unpaired_in_second = arr2_elements
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| !paired_in_second[*j]);
+    for (position, (_, b)) in (arr1_elements.len()..).zip(unpaired_in_second) {
+        diffs.push(ArrayDiff::ArrayValueInSecond(
+            position,
+            convert(b.value, options),
+        ));
+    }
+
+    JsonV::Array(same, diffs)
 }
 
-fn convert(v: &Value) -> JsonV {
+fn is_same_container_type(a: &Value, b: &Value) -> bool {
+    matches!(
+        (a, b),
+        (Value::Object(_), Value::Object(_)) | (Value::Array(_), Value::Array(_))
+    )
+}
+
+/// Integers are compared exactly, whatever their size. Anything else is compared as
+/// `f64`, so `1.0` equals `1` and `1e2` equals `100`.
+fn numbers_equal(n1: &Number, n2: &Number) -> bool {
+    let (s1, s2) = (n1.to_string(), n2.to_string());
+    let is_integer = |s: &str| !s.contains(['.', 'e', 'E']);
+    let is_zero = |s: &str| s.trim_start_matches('-') == "0";
+    if is_integer(&s1) && is_integer(&s2) {
+        s1 == s2 || (is_zero(&s1) && is_zero(&s2))
+    } else {
+        match (n1.as_f64(), n2.as_f64()) {
+            (Some(a), Some(b)) => a == b,
+            _ => s1 == s2,
+        }
+    }
+}
+
+fn convert(v: &Value, options: &DiffOptions) -> JsonV {
     match v {
         Value::Null => JsonV::Null(None),
         Value::Bool(b) => JsonV::Bool(*b, None),
-        Value::Number(n) => JsonV::Number(n.as_f64().unwrap_or(0.0), None),
-        Value::String(s) => JsonV::String(s.to_string(), None),
+        Value::Number(n) => JsonV::Number(n.clone(), None, None),
+        Value::String(s) => JsonV::String(s.to_string(), None, Vec::new()),
         Value::Object(o) => {
-            let mut map: BTreeMap<String, JsonV> = BTreeMap::new();
-            for k in o.keys() {
-                if let Some(v) = o.get(k) {
-                    map.insert(k.to_string(), convert(v));
+            let mut map: IndexMap<String, JsonV> = IndexMap::new();
+            for k in ordered_keys(o, &Map::new(), options.key_order) {
+                if let Some(v) = o.get(&k) {
+                    map.insert(k, convert(v, options));
                 }
             }
-            JsonV::Object(map, Vec::new())
+            let keys = map.keys().cloned().collect();
+            JsonV::Object(map, Vec::new(), keys)
         }
         Value::Array(b) => {
-            let converted_elements = b.iter().enumerate().map(|(i, v)| (i, convert(v))).collect();
+            let converted_elements = b
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (i, convert(v, options)))
+                .collect();
             JsonV::Array(converted_elements, Vec::new())
         }
     }
 }
 
-fn has_differences(j: &JsonV) -> bool {
+pub(crate) fn has_differences(j: &JsonV) -> bool {
     match j {
         JsonV::Null(st) if st.is_some() => true,
-        JsonV::String(_, st) if st.is_some() => true,
+        JsonV::TypeChanged(_, _, _) => true,
+        JsonV::Replaced(_) => true,
+        JsonV::String(_, st, _) if st.is_some() => true,
         JsonV::Bool(_, st) if st.is_some() => true,
-        JsonV::Number(_, st) if st.is_some() => true,
-        JsonV::Object(_, st) if !st.is_empty() => true,
+        JsonV::Number(_, st, _) if st.is_some() => true,
+        JsonV::Object(_, st, _) if !st.is_empty() => true,
         JsonV::Array(_, st) if !st.is_empty() => true,
         _ => false,
     }
 }
 
-fn a_intersection_complement_b<T: std::clone::Clone + std::cmp::Ord>(
-    a: Vec<T>,
-    b: Vec<T>,
-) -> Vec<T> {
-    let u = union(a, b.clone());
-    let mut u2 = u.clone();
-    u2.retain(|x| !b.contains(x));
+/// Splits two strings into the segments they share and the ones only in either of them.
+/// The common prefix and suffix are trimmed before aligning the rest with
+/// `edit_distance_linear`, which keeps long strings with small changes cheap and the
+/// memory linear in the length of the strings.
+pub fn string_segments(s1: &str, s2: &str, granularity: Granularity) -> Vec<StringSegment> {
+    let tokenize = |s: &str| -> Vec<String> {
+        match granularity {
+            Granularity::Chars => s.chars().map(|c| c.to_string()).collect(),
+            Granularity::Words => {
+                let mut tokens: Vec<String> = Vec::new();
+                for c in s.chars() {
+                    match tokens.last_mut() {
+                        Some(t) if t.ends_with(char::is_whitespace) == c.is_whitespace() => {
+                            t.push(c)
+                        }
+                        _ => tokens.push(c.to_string()),
+                    }
+                }
+                tokens
+            }
+        }
+    };
+    let (tokens1, tokens2) = (tokenize(s1), tokenize(s2));
+    let prefix = tokens1
+        .iter()
+        .zip(tokens2.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = tokens1[prefix..]
+        .iter()
+        .rev()
+        .zip(tokens2[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let middle1 = &tokens1[prefix..tokens1.len() - suffix];
+    let middle2 = &tokens2[prefix..tokens2.len() - suffix];
 
-    u2
+    let mut segments: Vec<StringSegment> = Vec::new();
+    let mut push = |segment: StringSegment| match (segments.last_mut(), segment) {
+        (Some(StringSegment::Same(s)), StringSegment::Same(t))
+        | (Some(StringSegment::InFirst(s)), StringSegment::InFirst(t))
+        | (Some(StringSegment::InSecond(s)), StringSegment::InSecond(t)) => s.push_str(&t),
+        (_, segment) => segments.push(segment),
+    };
+    push(StringSegment::Same(tokens1[..prefix].concat()));
+    for edit_type in edit_distance_linear(middle1, middle2) {
+        match edit_type {
+            EditType::Insert(j) => push(StringSegment::InSecond(middle2[j].clone())),
+            EditType::Delete(i) => push(StringSegment::InFirst(middle1[i].clone())),
+            EditType::Substitute(i, _, true) => push(StringSegment::Same(middle1[i].clone())),
+            EditType::Substitute(i, j, false) => {
+                push(StringSegment::InFirst(middle1[i].clone()));
+                push(StringSegment::InSecond(middle2[j].clone()));
+            }
+            EditType::Unknown => (),
+        }
+    }
+    push(StringSegment::Same(
+        tokens1[tokens1.len() - suffix..].concat(),
+    ));
+    segments.retain(|x| !matches!(x, StringSegment::Same(s) if s.is_empty()));
+
+    segments
 }
 
-fn union<T: std::clone::Clone + std::cmp::Ord>(s: Vec<T>, other: Vec<T>) -> Vec<T> {
-    let mut stack = s.clone();
-    for x in other {
-        stack.push(x)
+/// The union of the keys of both objects in the order given by `key_order`.
+fn ordered_keys(
+    a: &Map<String, Value>,
+    b: &Map<String, Value>,
+    key_order: KeyOrder,
+) -> Vec<String> {
+    let (first, second) = if key_order == KeyOrder::Second {
+        (b, a)
+    } else {
+        (a, b)
+    };
+    let mut keys: Vec<String> = first
+        .keys()
+        .chain(second.keys().filter(|x| !first.contains_key(*x)))
+        .cloned()
+        .collect();
+    if key_order == KeyOrder::Sorted {
+        keys.sort();
     }
-    stack.sort();
-    stack.dedup();
 
-    stack
+    keys
 }
 
 #[cfg(test)]
@@ -231,6 +1223,8 @@ mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
 
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
     #[test]
     fn test_same_object() -> Result<()> {
         let r = r#"
@@ -247,22 +1241,29 @@ mod tests {
         }"#;
         let ja: Value = serde_json::from_str(r)?;
         let jb: Value = serde_json::from_str(r2)?;
-        let c2 = diff_rec(&ja, &jb);
+        let c2 = diff_rec(&ja, &jb, &DiffOptions::default(), &[]);
 
-        let mut map2: BTreeMap<String, JsonV> = BTreeMap::new();
+        let mut map2: IndexMap<String, JsonV> = IndexMap::new();
         map2.insert(
             "f1".to_string(),
-            JsonV::String("v2".to_string(), None),
+            JsonV::String("v2".to_string(), None, vec![]),
         );
         map2.insert(
             "f2".to_string(),
-            JsonV::String("v0".to_string(), None),
+            JsonV::String("v0".to_string(), None, vec![]),
         );
         map2.insert(
             "f3".to_string(),
-            JsonV::Array(vec![(0, JsonV::Number(1.0, None)), (1, JsonV::Number(2.0, None))], vec![]),
+            JsonV::Array(
+                vec![
+                    (0, JsonV::Number(1.into(), None, None)),
+                    (1, JsonV::Number(2.into(), None, None)),
+                ],
+                vec![],
+            ),
         );
-        let e = JsonV::Object(map2, vec![]);
+        let keys = vec!["f1".to_string(), "f2".to_string(), "f3".to_string()];
+        let e = JsonV::Object(map2, vec![], keys);
         assert_eq!(c2.to_string(), e.to_string());
         Ok(())
     }
@@ -303,9 +1304,9 @@ mod tests {
 
         let ja: Value = serde_json::from_str(r)?;
         let jb: Value = serde_json::from_str(r2)?;
-        let c2 = diff_rec(&ja, &jb);
+        let c2 = diff_rec(&ja, &jb, &DiffOptions::default(), &[]);
 
-        println!("");
+        println!();
         println!("{:?}", c2);
         Ok(())
     }
@@ -343,10 +1344,563 @@ mod tests {
         // diff(r, r2);
         let ja: Value = serde_json::from_str(r)?;
         let jb: Value = serde_json::from_str(r2)?;
-        let c2 = diff_rec(&ja, &jb);
+        let c2 = diff_rec(&ja, &jb, &DiffOptions::default(), &[]);
 
-        println!("");
+        println!();
         println!("{:?}", c2);
         Ok(())
     }
+
+    #[test]
+    fn test_array_identity_key() -> Result<()> {
+        let r = r#"[{"id": 1, "v": "a"}, {"id": 2, "v": "b"}, {"id": 3, "v": "c"}]"#;
+        let r2 = r#"[{"id": 1, "v": "a"}, {"id": 2, "v": "x"}, {"id": 4, "v": "d"}]"#;
+        let options = DiffOptions {
+            identity_keys: vec![(PathPattern::any(), "id".to_string())],
+            ..Default::default()
+        };
+        let c2 = diff_with_options(r, r2, &options)?;
+
+        if let JsonV::Array(same, diffs) = c2 {
+            assert_eq!(same.len(), 1);
+            assert_eq!(diffs.len(), 3);
+            assert!(matches!(
+                &diffs[0],
+                ArrayDiff::ArrayValueDiff(1, JsonV::Object(_, d, _)) if d.len() == 1
+            ));
+            assert!(matches!(&diffs[1], ArrayDiff::ArrayValueInFirst(2, _)));
+            assert!(matches!(&diffs[2], ArrayDiff::ArrayValueInSecond(3, _)));
+        } else {
+            panic!("Expected an array");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_array_identity_key_reordered() -> Result<()> {
+        use crate::apply::apply_patch;
+        use crate::patch::json_patch;
+        let options = DiffOptions {
+            identity_keys: vec![(PathPattern::any(), "id".to_string())],
+            ..Default::default()
+        };
+        let cases = [
+            (
+                r#"[{"id": 1, "v": 1}, {"id": 2, "v": 2}]"#,
+                r#"[{"id": 2, "v": 2}, {"id": 1, "v": 1}]"#,
+            ),
+            (r#"[1, {"id": 1}]"#, r#"[{"id": 1}, 1]"#),
+            (
+                r#"[null, {"id": 1, "y": false}, false, 3]"#,
+                r#"[null, 0, {"id": 1, "z": null}, 3]"#,
+            ),
+            (
+                r#"[0, {"id": 1, "v": 1}, {"id": 2, "v": 2}, {"id": 3}, 4]"#,
+                r#"[{"id": 3}, 0, {"id": 2, "v": 2}, {"id": 1, "v": 5}, 6]"#,
+            ),
+        ];
+        for (r, r2) in cases {
+            let (a, b): (Value, Value) = (serde_json::from_str(r)?, serde_json::from_str(r2)?);
+            let c2 = diff_with_options(r, r2, &options)?;
+            assert!(has_differences(&c2));
+            assert_eq!(c2.first_value(), a);
+            assert_eq!(c2.second_value(), b);
+            assert_eq!(apply_patch(&a, &json_patch(&c2))?, b);
+        }
+
+        let (r, r2) = cases[3];
+        match &diff_with_options(r, r2, &options)? {
+            JsonV::Array(_, diffs) => {
+                // The record with id 1 moved past the one with id 2 and changed
+                assert!(diffs.iter().any(|d| matches!(
+                    d,
+                    ArrayDiff::ArrayValueMoved(_, 1, JsonV::Object(_, d, _)) if d.len() == 1
+                )));
+            }
+            _ => panic!("Expected an array"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_substituted_elements_are_diffed() -> Result<()> {
+        let r = r#"[{"f3": "v3", "f4": [1, 2, 3]}, {"f3": "v3", "f4": [1, 2, 3, 4]}]"#;
+        let r2 = r#"[{"f3": "v3", "f4": [1, 2, 3]}, {"f3": "v3", "f4": [1, 2, 3]}]"#;
+        let c2 = diff(r, r2)?;
+
+        if let JsonV::Array(same, diffs) = c2 {
+            assert_eq!(same.len(), 1);
+            assert_eq!(diffs.len(), 1);
+            if let ArrayDiff::ArrayValueDiff(1, JsonV::Object(similar, d, _)) = &diffs[0] {
+                assert!(similar.contains_key("f3"));
+                assert!(matches!(
+                    &d[..],
+                    [ObjectDiff::ObjectValueDiff(k, JsonV::Array(_, inner))]
+                        if k == "f4" && inner.len() == 1
+                ));
+            } else {
+                panic!("Expected a nested object diff, got {:?}", diffs[0]);
+            }
+        } else {
+            panic!("Expected an array");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_integers() -> Result<()> {
+        let r = r#"{"id": 9007199254740993, "big": 12345678901234567890123, "f": 1.0}"#;
+        let r2 = r#"{"id": 9007199254740992, "big": 12345678901234567890123, "f": 1}"#;
+        let c2 = diff(r, r2)?;
+
+        if let JsonV::Object(similar, diffs, _) = c2 {
+            assert!(matches!(
+                &similar["big"],
+                JsonV::Number(n, None, None) if n.to_string() == "12345678901234567890123"
+            ));
+            assert!(similar.contains_key("f"));
+            assert!(matches!(&diffs[..], [ObjectDiff::ObjectValueDiff(k, _)] if k == "id"));
+        } else {
+            panic!("Expected an object");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_number_tolerance() -> Result<()> {
+        let r = r#"{"a": 0.30000000000000004, "b": [100.0, 2.0], "c": 1.5}"#;
+        let r2 = r#"{"a": 0.3, "b": [100.5, 2.0], "c": 1.6}"#;
+        let options = DiffOptions {
+            number_tolerances: vec![
+                (
+                    PathPattern::parse("/b/*")?,
+                    Tolerance {
+                        absolute: 0.0,
+                        relative: 0.01,
+                    },
+                ),
+                (
+                    PathPattern::any(),
+                    Tolerance {
+                        absolute: 1e-9,
+                        relative: 0.0,
+                    },
+                ),
+            ],
+            ..Default::default()
+        };
+        let c2 = diff_with_options(r, r2, &options)?;
+
+        if let JsonV::Object(similar, diffs, _) = c2 {
+            assert!(matches!(
+                &similar["a"],
+                JsonV::Number(_, None, Some(w)) if w.second.to_string() == "0.3" && w.delta < 0.0
+            ));
+            assert!(matches!(
+                &similar["b"],
+                JsonV::Array(same, d) if same.len() == 2 && d.is_empty()
+            ));
+            assert_eq!(similar["b"].second_value(), serde_json::json!([100.5, 2.0]));
+            assert_eq!(
+                similar["b"].invert().second_value(),
+                serde_json::json!([100.0, 2.0])
+            );
+            assert!(matches!(&diffs[..], [ObjectDiff::ObjectValueDiff(k, _)] if k == "c"));
+        } else {
+            panic!("Expected an object");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_order() -> Result<()> {
+        let r = r#"{"b": 1, "a": 2, "c": 3, "z": {"y": 1, "x": 2}}"#;
+        let r2 = r#"{"a": 2, "b": 1, "d": 4, "z": {"y": 1, "x": 2}}"#;
+        let options = DiffOptions {
+            key_order: KeyOrder::First,
+            report_key_reordering: true,
+            ..Default::default()
+        };
+        let c2 = diff_with_options(r, r2, &options)?;
+
+        if let JsonV::Object(similar, diffs, _) = c2 {
+            assert_eq!(similar.keys().collect::<Vec<_>>(), vec!["b", "a", "z"]);
+            assert!(matches!(&similar["z"], JsonV::Object(m, _, _) if m.keys().eq(["y", "x"])));
+            assert!(matches!(
+                &diffs[..],
+                [
+                    ObjectDiff::ObjectKeyPresent(c, _),
+                    ObjectDiff::ObjectKeyMissing(d, _),
+                    ObjectDiff::ObjectKeysReordered(first, second),
+                ] if c == "c" && d == "d" && first[..2] == ["b", "a"] && second[..2] == ["a", "b"]
+            ));
+        } else {
+            panic!("Expected an object");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignore_rules() -> Result<()> {
+        let r = r#"{"meta": {"timestamp": 1}, "items": [{"etag": "a", "requestId": 1}]}"#;
+        let r2 = r#"{"meta": {"timestamp": 2}, "items": [{"etag": "b"}], "requestId": 5}"#;
+        let options = DiffOptions {
+            ignore: vec![
+                PathPattern::parse("$.meta.timestamp")?,
+                PathPattern::parse("$..requestId")?,
+                PathPattern::parse("/items/*/etag")?,
+            ],
+            ..Default::default()
+        };
+        let c2 = diff_with_options(r, r2, &options)?;
+
+        assert!(!has_differences(&c2));
+        if let JsonV::Object(similar, _, _) = c2 {
+            assert!(matches!(&similar["requestId"], JsonV::Ignored(_)));
+            assert!(matches!(
+                &similar["meta"],
+                JsonV::Object(m, _, _) if matches!(m["timestamp"], JsonV::Ignored(_))
+            ));
+        } else {
+            panic!("Expected an object");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unordered_arrays() -> Result<()> {
+        let r =
+            r#"{"tags": ["a", "b", "b", "c"], "users": [{"n": "x", "r": 1}, {"n": "y", "r": 2}]}"#;
+        let r2 = r#"{"tags": ["c", "b", "a"], "users": [{"n": "y", "r": 3}, {"n": "x", "r": 1}]}"#;
+        let options = DiffOptions {
+            unordered_arrays: vec![PathPattern::parse("/tags")?, PathPattern::parse("/users")?],
+            ..Default::default()
+        };
+        let c2 = diff_with_options(r, r2, &options)?;
+
+        if let JsonV::Object(_, diffs, _) = c2 {
+            assert!(matches!(
+                &diffs[..],
+                [
+                    ObjectDiff::ObjectValueDiff(_, JsonV::Array(tags_same, tags_diffs)),
+                    ObjectDiff::ObjectValueDiff(_, JsonV::Array(users_same, users_diffs)),
+                ] if tags_same.len() == 3
+                    && matches!(&tags_diffs[..], [ArrayDiff::ArrayValueInFirst(2, _)])
+                    && users_same.len() == 1
+                    && matches!(&users_diffs[..], [ArrayDiff::ArrayValueDiff(1, _)])
+            ));
+        } else {
+            panic!("Expected an object");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_segments() {
+        assert_eq!(
+            string_segments("the quick fox", "the slow fox", Granularity::Words),
+            vec![
+                StringSegment::Same("the ".to_string()),
+                StringSegment::InFirst("quick".to_string()),
+                StringSegment::InSecond("slow".to_string()),
+                StringSegment::Same(" fox".to_string()),
+            ]
+        );
+        assert_eq!(
+            string_segments("abc", "abxc", Granularity::Chars),
+            vec![
+                StringSegment::Same("ab".to_string()),
+                StringSegment::InSecond("x".to_string()),
+                StringSegment::Same("c".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_change() -> Result<()> {
+        let c2 = diff(
+            r#"{"a": "text", "b": null}"#,
+            r#"{"a": {"x": 1}, "b": null}"#,
+        )?;
+
+        if let JsonV::Object(similar, diffs, _) = c2 {
+            assert!(matches!(similar["b"], JsonV::Null(None)));
+            assert!(matches!(
+                &diffs[..],
+                [ObjectDiff::ObjectValueDiff(k, JsonV::TypeChanged(JsonType::String, JsonType::Object, _))]
+                    if k == "a"
+            ));
+        } else {
+            panic!("Expected an object");
+        }
+        assert_eq!(JsonType::Bool.to_string(), "boolean");
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            diff("{\"a\": 1}", "{\"a\": }"),
+            Err(Error::RightParse(ParseError {
+                line: 1,
+                column: 7,
+                ..
+            }))
+        ));
+        assert!(matches!(diff("[1,", "[]"), Err(Error::LeftParse(_))));
+        let options = DiffOptions {
+            max_input_len: Some(4),
+            ..Default::default()
+        };
+        assert!(matches!(
+            diff_with_options("[1]", "[1, 2]", &options),
+            Err(Error::Limit(_))
+        ));
+    }
+
+    #[test]
+    fn test_first_and_second_value() -> Result<()> {
+        let r = r#"{"a": [1, 2, {"b": "x"}], "c": true, "d": 1}"#;
+        let r2 = r#"{"a": [2, 3, {"b": "y"}], "c": "s", "e": null}"#;
+        let c2 = diff(r, r2)?;
+
+        assert_eq!(c2.first_value(), serde_json::from_str::<Value>(r)?);
+        assert_eq!(c2.second_value(), serde_json::from_str::<Value>(r2)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invert() -> Result<()> {
+        let r = r#"{"a": [1, 2, {"b": "x"}], "c": true, "d": 1, "s": "one two"}"#;
+        let r2 = r#"{"a": [2, 3, {"b": "y"}], "c": "s", "e": null, "s": "one three"}"#;
+        let options = DiffOptions {
+            string_granularity: Some(Granularity::Words),
+            ..Default::default()
+        };
+        let inverted = diff_with_options(r, r2, &options)?.invert();
+
+        assert_eq!(inverted.first_value(), serde_json::from_str::<Value>(r2)?);
+        assert_eq!(inverted.second_value(), serde_json::from_str::<Value>(r)?);
+        assert_eq!(
+            crate::html_generator::generate(inverted),
+            crate::html_generator::generate(diff_with_options(r2, r, &options)?)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_equivalence() -> Result<()> {
+        let options = DiffOptions {
+            missing_equivalences: vec![
+                (
+                    PathPattern::parse("/meta/**")?,
+                    MissingEquivalence {
+                        empty_containers: true,
+                    },
+                ),
+                (PathPattern::any(), MissingEquivalence::default()),
+            ],
+            ..Default::default()
+        };
+        let r = r#"{"a": null, "meta": {"tags": [], "x": {}}, "list": [{"b": null}], "c": []}"#;
+        let r2 = r#"{"meta": {"y": null}, "list": [{}], "d": 0}"#;
+        let c2 = diff_with_options(r, r2, &options)?;
+
+        match &c2 {
+            JsonV::Object(similarities, differences, _) => {
+                let keys: Vec<&str> = differences
+                    .iter()
+                    .map(|d| match d {
+                        ObjectDiff::ObjectKeyPresent(k, _) | ObjectDiff::ObjectKeyMissing(k, _) => {
+                            k.as_str()
+                        }
+                        _ => panic!("unexpected difference {:?}", d),
+                    })
+                    .collect();
+                assert_eq!(keys, vec!["c", "d"]);
+                assert!(!has_differences(&similarities["meta"]));
+                assert!(!has_differences(&similarities["list"]));
+            }
+            _ => panic!("expected an object"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_equivalence() -> Result<()> {
+        let equivalence = StringEquivalence {
+            ignore_case: true,
+            collapse_whitespace: true,
+            normalization: Some(Normalization::Nfc),
+            ..Default::default()
+        };
+        let options = DiffOptions {
+            string_equivalences: vec![(PathPattern::parse("/names/*")?, equivalence)],
+            ..Default::default()
+        };
+        let r = r#"{"names": ["Caf\u00e9  Bar", "b"], "other": "A"}"#;
+        let r2 = r#"{"names": ["cafe\u0301 bar", "B"], "other": "a"}"#;
+        let c2 = diff_with_options(r, r2, &options)?;
+
+        match &c2 {
+            JsonV::Object(similarities, differences, _) => {
+                assert!(matches!(
+                    &differences[..],
+                    [ObjectDiff::ObjectValueDiff(k, _)] if k == "other"
+                ));
+                assert_eq!(
+                    similarities["names"].first_value(),
+                    serde_json::json!(["Caf\u{e9}  Bar", "b"])
+                );
+            }
+            _ => panic!("expected an object"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_comparators() -> Result<()> {
+        // Versions are equal when they only differ in trailing ".0" components
+        let version = |a: &Value, b: &Value| match (a.as_str(), b.as_str()) {
+            (Some(v1), Some(v2)) if v1.trim_end_matches(".0") == v2.trim_end_matches(".0") => {
+                Comparison::Equal
+            }
+            _ => Comparison::Undecided,
+        };
+        let options = DiffOptions {
+            comparators: vec![
+                (PathPattern::parse("$..version")?, Rc::new(version)),
+                (
+                    PathPattern::parse("/opaque")?,
+                    Rc::new(|_: &Value, _: &Value| Comparison::Different),
+                ),
+            ],
+            ..Default::default()
+        };
+        let r = r#"{"version": "1.2", "deps": [{"version": "2"}], "opaque": 1, "n": 1}"#;
+        let r2 = r#"{"version": "1.2.0", "deps": [{"version": "2.1"}], "opaque": 1, "n": 2}"#;
+        let c2 = diff_with_options(r, r2, &options)?;
+
+        match &c2 {
+            JsonV::Object(_, differences, _) => {
+                let keys: Vec<&str> = differences
+                    .iter()
+                    .filter_map(|d| match d {
+                        ObjectDiff::ObjectValueDiff(k, _) => Some(k.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                assert_eq!(keys, vec!["deps", "n", "opaque"]);
+            }
+            _ => panic!("expected an object"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_different_containers() -> Result<()> {
+        let different = |_: &Value, _: &Value| Comparison::Different;
+        let options = DiffOptions {
+            comparators: vec![
+                (
+                    PathPattern::parse("/same")?,
+                    Rc::new(|_: &Value, _: &Value| Comparison::Undecided),
+                ),
+                (PathPattern::parse("/*")?, Rc::new(different)),
+            ],
+            ..Default::default()
+        };
+        let r = r#"{"same": {"a": 1}, "changed": [1, 2]}"#;
+        let r2 = r#"{"same": {"a": 1}, "changed": [1, 3]}"#;
+        let c2 = diff_with_options(r, r2, &options)?;
+
+        match &c2 {
+            JsonV::Object(_, differences, _) => {
+                assert!(matches!(
+                    &differences[..],
+                    [
+                        ObjectDiff::ObjectValueDiff(k1, JsonV::Array(_, _)),
+                        ObjectDiff::ObjectValueDiff(k2, JsonV::Replaced(_)),
+                    ] if k1 == "changed" && k2 == "same"
+                ));
+            }
+            _ => panic!("expected an object"),
+        }
+        let replaced = crate::changes::changes(&c2)
+            .into_iter()
+            .find(|c| c.left.as_deref() == Some("/same"))
+            .map(|c| c.kind);
+        assert_eq!(replaced, Some(crate::changes::ChangeKind::Changed));
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_moves() -> Result<()> {
+        let options = DiffOptions {
+            move_threshold: Some(0.5),
+            ..Default::default()
+        };
+        let r = r#"[{"a": 1, "b": 2}, 1, 2, 3, "x"]"#;
+        let r2 = r#"[1, 2, 3, {"a": 1, "b": 3}, "y"]"#;
+        let c2 = diff_with_options(r, r2, &options)?;
+
+        match &c2 {
+            JsonV::Array(_, differences) => {
+                let moves: Vec<(&str, usize)> = differences
+                    .iter()
+                    .filter_map(|d| match d {
+                        ArrayDiff::ArrayValueMovedAway(_, to, _) => Some(("to", *to)),
+                        ArrayDiff::ArrayValueMoved(_, from, v) if has_differences(v) => {
+                            Some(("from", *from))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                assert_eq!(moves, vec![("to", 3), ("from", 0)]);
+            }
+            _ => panic!("expected an array"),
+        }
+        assert_eq!(c2.first_value(), serde_json::from_str::<Value>(r)?);
+        assert_eq!(c2.second_value(), serde_json::from_str::<Value>(r2)?);
+        assert_eq!(c2.invert().first_value(), serde_json::from_str::<Value>(r2)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_renames() -> Result<()> {
+        let options = DiffOptions {
+            rename_threshold: Some(0.5),
+            ..Default::default()
+        };
+        let r = r#"{"name": "x", "address": {"street": "a", "city": "b"}, "gone": 1}"#;
+        let r2 = r#"{"title": "x", "location": {"street": "a", "city": "c"}, "new": true}"#;
+        let c2 = diff_with_options(r, r2, &options)?;
+
+        match &c2 {
+            JsonV::Object(_, differences, _) => {
+                let keys: Vec<(&str, &str, bool)> = differences
+                    .iter()
+                    .filter_map(|d| match d {
+                        ObjectDiff::ObjectKeyRenamed(old, new, v) => {
+                            Some((old.as_str(), new.as_str(), has_differences(v)))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                assert_eq!(
+                    keys,
+                    vec![("name", "title", false), ("address", "location", true)]
+                );
+                assert_eq!(differences.len(), 4);
+            }
+            _ => panic!("expected an object"),
+        }
+        assert_eq!(c2.first_value(), serde_json::from_str::<Value>(r)?);
+        assert_eq!(c2.second_value(), serde_json::from_str::<Value>(r2)?);
+        assert_eq!(
+            c2.invert().second_value(),
+            serde_json::from_str::<Value>(r)?
+        );
+        Ok(())
+    }
 }
@@ -1,9 +1,20 @@
+use error::Error;
 use std::result::Result;
 use wasm_bindgen::prelude::*;
 
-mod edit_distance;
-mod html_generator;
-mod json_diff;
+pub mod apply;
+pub mod changes;
+pub mod comparator;
+pub mod edit_distance;
+pub mod error;
+pub mod html_generator;
+pub mod json_diff;
+pub mod merge;
+pub mod patch;
+pub mod path;
+pub mod similarity;
+pub mod stats;
+pub mod stream;
 
 extern crate web_sys;
 
@@ -15,13 +26,101 @@ macro_rules! log {
 }
 
 #[wasm_bindgen]
-pub fn find_diff(arg1: &str, arg2: &str) -> String {
-    if let Result::Ok(json) = json_diff::diff(arg1, arg2) {
-        log!("{:?}", json);
-        let html_str = html_generator::generate(json);
-
-        html_str
-    } else {
-        "error!".to_string()
+pub fn find_diff(arg1: &str, arg2: &str) -> Result<String, JsValue> {
+    let json = json_diff::diff(arg1, arg2).map_err(to_js_error)?;
+    log!("{:?}", json);
+
+    Ok(html_generator::generate(json))
+}
+
+/// Counts the changes between the documents. The result holds the fields of
+/// `stats::Counts` in camelCase plus `total`, and `sections` with the counts and `key`
+/// of each changed top-level key.
+#[wasm_bindgen]
+pub fn diff_summary(arg1: &str, arg2: &str) -> Result<JsValue, JsValue> {
+    let json = json_diff::diff(arg1, arg2).map_err(to_js_error)?;
+    let summary = stats::summary(&json);
+
+    let sections = js_sys::Array::new();
+    for (key, counts) in &summary.sections {
+        let section = counts_to_js(counts);
+        js_sys::Reflect::set(&section, &"key".into(), &key.as_str().into())?;
+        sections.push(&section);
     }
+    let result = counts_to_js(&summary.total);
+    js_sys::Reflect::set(&result, &"sections".into(), &sections)?;
+
+    Ok(result.into())
+}
+
+/// How similar the documents are, from 0.0 for nothing in common to 1.0 for equal.
+#[wasm_bindgen]
+pub fn diff_similarity(arg1: &str, arg2: &str) -> Result<f64, JsValue> {
+    let json = json_diff::diff(arg1, arg2).map_err(to_js_error)?;
+
+    Ok(similarity::similarity(&json))
+}
+
+/// The similarity of every value present in both documents, as an object from JSON
+/// Pointers into the first document to scores.
+#[wasm_bindgen]
+pub fn subtree_similarities(arg1: &str, arg2: &str) -> Result<JsValue, JsValue> {
+    let json = json_diff::diff(arg1, arg2).map_err(to_js_error)?;
+    let result = js_sys::Object::new();
+    for (pointer, score) in similarity::subtree_similarities(&json) {
+        js_sys::Reflect::set(&result, &pointer.as_str().into(), &score.into())?;
+    }
+
+    Ok(result.into())
+}
+
+fn counts_to_js(counts: &stats::Counts) -> js_sys::Object {
+    let object = js_sys::Object::new();
+    let fields = [
+        ("total", counts.total()),
+        ("addedKeys", counts.added_keys),
+        ("removedKeys", counts.removed_keys),
+        ("changed", counts.changed),
+        ("typeChanges", counts.type_changes),
+        ("arrayInsertions", counts.array_insertions),
+        ("arrayDeletions", counts.array_deletions),
+        ("reordered", counts.reordered),
+        ("moved", counts.moved),
+        ("renamedKeys", counts.renamed_keys),
+        ("maxDepth", counts.max_depth),
+    ];
+    for (key, value) in fields.iter() {
+        js_sys::Reflect::set(&object, &(*key).into(), &(*value as u32).into()).ok();
+    }
+
+    object
+}
+
+/// Converts the error to a javascript `Error`, with a `kind` of "leftParse",
+/// "rightParse", "limit", "unrepresentable", "apply", "io", "pattern", "unsupported" or
+/// "internal" and the position of parse errors in `line`, `column` and `snippet`.
+fn to_js_error(error: Error) -> JsValue {
+    let js_error = js_sys::Error::new(&error.to_string());
+    let set = |key: &str, value: JsValue| {
+        js_sys::Reflect::set(&js_error, &key.into(), &value).ok();
+    };
+    let (kind, parse_error) = match &error {
+        Error::LeftParse(e) => ("leftParse", Some(e)),
+        Error::RightParse(e) => ("rightParse", Some(e)),
+        Error::Limit(_) => ("limit", None),
+        Error::Unrepresentable(_, _) => ("unrepresentable", None),
+        Error::Apply(_) => ("apply", None),
+        Error::Io(_) => ("io", None),
+        Error::Pattern(_, _) => ("pattern", None),
+        Error::Unsupported(_) => ("unsupported", None),
+        Error::Internal(_) => ("internal", None),
+    };
+    set("kind", kind.into());
+    if let Some(e) = parse_error {
+        set("line", (e.line as u32).into());
+        set("column", (e.column as u32).into());
+        set("snippet", e.snippet.as_str().into());
+    }
+
+    js_error.into()
 }
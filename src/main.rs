@@ -1,8 +1,5 @@
-use serde_json::Result;
-
-mod edit_distance;
-mod html_generator;
-mod json_diff;
+use jsondiff::error::Result;
+use jsondiff::{html_generator, json_diff};
 
 fn main() -> Result<()> {
     println!("Hello, world!");
@@ -43,9 +40,3 @@ fn main() -> Result<()> {
 
     Ok(())
 }
-
-#[cfg(test)]
-mod tests {
-    // Note this useful idiom: importing names from outer (for mod tests) scope.
-    use super::*;
-}
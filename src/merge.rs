@@ -0,0 +1,294 @@
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use super::changes::pointer_token;
+use super::json_diff::{
+    array_entries, diff_values, has_differences, ArrayDiff, ArrayEntry, DiffOptions, JsonV,
+    ObjectDiff,
+};
+
+/// A path changed differently on both sides. The pointer is an RFC 6901 JSON Pointer
+/// into the base document, and a missing candidate means that side deleted the value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    pub pointer: String,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+/// The merged document, which holds our side of every conflict.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Merge {
+    pub value: Value,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// What one side did to a value of the base document.
+#[derive(Clone, Copy)]
+enum Edit<'a> {
+    Unchanged,
+    Removed,
+    Changed(&'a JsonV),
+}
+
+/// Merges the changes from `base` to `ours` and from `base` to `theirs`.
+pub fn merge(base: &Value, ours: &Value, theirs: &Value) -> Merge {
+    merge_with_options(base, ours, theirs, &DiffOptions::default())
+}
+
+pub fn merge_with_options(
+    base: &Value,
+    ours: &Value,
+    theirs: &Value,
+    options: &DiffOptions,
+) -> Merge {
+    let ours_diff = diff_values(base, ours, options);
+    let theirs_diff = diff_values(base, theirs, options);
+    let mut conflicts = Vec::new();
+    let value = merge_rec(base, &ours_diff, &theirs_diff, "", &mut conflicts);
+
+    Merge { value, conflicts }
+}
+
+fn merge_rec(
+    base: &Value,
+    ours: &JsonV,
+    theirs: &JsonV,
+    pointer: &str,
+    conflicts: &mut Vec<Conflict>,
+) -> Value {
+    if !has_differences(theirs) {
+        return ours.second_value();
+    }
+    if !has_differences(ours) {
+        return theirs.second_value();
+    }
+    match (base, ours, theirs) {
+        (
+            Value::Object(base_obj),
+            JsonV::Object(_, ours_diffs, _),
+            JsonV::Object(_, theirs_diffs, _),
+        ) => {
+            let (ours_edits, ours_added) = key_edits(ours_diffs);
+            let (theirs_edits, theirs_added) = key_edits(theirs_diffs);
+            let mut merged = Map::new();
+            for (k, base_value) in base_obj {
+                let child = format!("{}/{}", pointer, pointer_token(k));
+                let (ours_edit, theirs_edit) =
+                    (key_edit(&ours_edits, k), key_edit(&theirs_edits, k));
+                if let Some(v) = merge_edits(base_value, ours_edit, theirs_edit, &child, conflicts)
+                {
+                    merged.insert(k.to_string(), v);
+                }
+            }
+            for (k, v) in &ours_added {
+                match theirs_added.iter().find(|(key, _)| key == k) {
+                    Some((_, other)) if other != v => conflicts.push(Conflict {
+                        pointer: format!("{}/{}", pointer, pointer_token(k)),
+                        ours: Some(v.clone()),
+                        theirs: Some(other.clone()),
+                    }),
+                    _ => (),
+                }
+                merged.insert(k.to_string(), v.clone());
+            }
+            for (k, v) in theirs_added {
+                if !merged.contains_key(k) {
+                    merged.insert(k.to_string(), v);
+                }
+            }
+            Value::Object(merged)
+        }
+        (
+            Value::Array(base_arr),
+            JsonV::Array(ours_same, ours_diffs),
+            JsonV::Array(theirs_same, theirs_diffs),
+        ) => {
+            let (ours_inserted, ours_edits) = element_edits(ours_same, ours_diffs, base_arr.len());
+            let (theirs_inserted, theirs_edits) =
+                element_edits(theirs_same, theirs_diffs, base_arr.len());
+            let mut merged = Vec::new();
+            for i in 0..=base_arr.len() {
+                let child = format!("{}/{}", pointer, i);
+                // Insertions in front of the i:th element of the base
+                let (ours_slot, theirs_slot) = (&ours_inserted[i], &theirs_inserted[i]);
+                if ours_slot.is_empty() {
+                    merged.extend(theirs_slot.iter().cloned());
+                } else {
+                    if !theirs_slot.is_empty() && ours_slot != theirs_slot {
+                        conflicts.push(Conflict {
+                            pointer: child.to_string(),
+                            ours: Some(Value::Array(ours_slot.clone())),
+                            theirs: Some(Value::Array(theirs_slot.clone())),
+                        });
+                    }
+                    merged.extend(ours_slot.iter().cloned());
+                }
+                if i < base_arr.len() {
+                    if let Some(v) = merge_edits(
+                        &base_arr[i],
+                        ours_edits[i],
+                        theirs_edits[i],
+                        &child,
+                        conflicts,
+                    ) {
+                        merged.push(v);
+                    }
+                }
+            }
+            Value::Array(merged)
+        }
+        _ if ours.second_value() == theirs.second_value() => ours.second_value(),
+        _ => {
+            conflicts.push(Conflict {
+                pointer: pointer.to_string(),
+                ours: Some(ours.second_value()),
+                theirs: Some(theirs.second_value()),
+            });
+            ours.second_value()
+        }
+    }
+}
+
+/// The merged value, or None if it is deleted.
+fn merge_edits(
+    base: &Value,
+    ours: Edit,
+    theirs: Edit,
+    pointer: &str,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<Value> {
+    match (ours, theirs) {
+        (Edit::Unchanged, Edit::Unchanged) => Some(base.clone()),
+        (Edit::Unchanged, Edit::Changed(v)) | (Edit::Changed(v), Edit::Unchanged) => {
+            Some(v.second_value())
+        }
+        (Edit::Changed(o), Edit::Changed(t)) => Some(merge_rec(base, o, t, pointer, conflicts)),
+        (Edit::Removed, Edit::Changed(t)) => {
+            conflicts.push(Conflict {
+                pointer: pointer.to_string(),
+                ours: None,
+                theirs: Some(t.second_value()),
+            });
+            None
+        }
+        (Edit::Changed(o), Edit::Removed) => {
+            conflicts.push(Conflict {
+                pointer: pointer.to_string(),
+                ours: Some(o.second_value()),
+                theirs: None,
+            });
+            Some(o.second_value())
+        }
+        _ => None,
+    }
+}
+
+/// The edits to the keys of the base object and the keys added to it.
+fn key_edits(differences: &[ObjectDiff]) -> (HashMap<&str, Edit<'_>>, Vec<(&str, Value)>) {
+    let mut edits = HashMap::new();
+    let mut added = Vec::new();
+    for d in differences {
+        match d {
+            ObjectDiff::ObjectKeyPresent(k, _) => {
+                edits.insert(k.as_str(), Edit::Removed);
+            }
+            ObjectDiff::ObjectValueDiff(k, v) => {
+                edits.insert(k.as_str(), Edit::Changed(v));
+            }
+            ObjectDiff::ObjectKeyMissing(k, v) => added.push((k.as_str(), v.second_value())),
+            // A rename is merged as a removal and an addition
+            ObjectDiff::ObjectKeyRenamed(old, new, v) => {
+                edits.insert(old.as_str(), Edit::Removed);
+                added.push((new.as_str(), v.second_value()));
+            }
+            ObjectDiff::ObjectKeysReordered(_, _) => (),
+        }
+    }
+
+    (edits, added)
+}
+
+fn key_edit<'a>(edits: &HashMap<&str, Edit<'a>>, key: &str) -> Edit<'a> {
+    edits.get(key).copied().unwrap_or(Edit::Unchanged)
+}
+
+/// The elements inserted in front of each element of the base array (and after the
+/// last one), and the edits to each element of the base array.
+fn element_edits<'a>(
+    same: &'a [(usize, JsonV)],
+    differences: &'a [ArrayDiff],
+    len: usize,
+) -> (Vec<Vec<Value>>, Vec<Edit<'a>>) {
+    let mut inserted = vec![Vec::new(); len + 1];
+    let mut edits = vec![Edit::Unchanged; len];
+    let mut index = 0;
+    for entry in array_entries(same, differences) {
+        match entry {
+            ArrayEntry::Same(_) => index += 1,
+            // A move is merged as a deletion and an insertion
+            ArrayEntry::Diff(ArrayDiff::ArrayValueInSecond(_, v))
+            | ArrayEntry::Diff(ArrayDiff::ArrayValueMoved(_, _, v)) => {
+                inserted[index].push(v.second_value())
+            }
+            ArrayEntry::Diff(ArrayDiff::ArrayValueInFirst(_, _))
+            | ArrayEntry::Diff(ArrayDiff::ArrayValueMovedAway(_, _, _)) => {
+                edits[index] = Edit::Removed;
+                index += 1;
+            }
+            ArrayEntry::Diff(ArrayDiff::ArrayValueDiff(_, v)) => {
+                edits[index] = Edit::Changed(v);
+                index += 1;
+            }
+        }
+    }
+
+    (inserted, edits)
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge() {
+        let base = json!({"a": 1, "b": {"c": "x", "d": [1, 2, 3]}, "gone": 0});
+        let ours = json!({"a": 2, "b": {"c": "x", "d": [0, 1, 2, 3]}, "gone": 0, "new": 1});
+        let theirs = json!({"a": 1, "b": {"c": "y", "d": [1, 2, 3, 4]}});
+        let merge = merge(&base, &ours, &theirs);
+
+        assert_eq!(
+            merge.value,
+            json!({"a": 2, "b": {"c": "y", "d": [0, 1, 2, 3, 4]}, "new": 1})
+        );
+        assert!(merge.conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_merge_conflicts() {
+        let base = json!({"a": 1, "list": [{"x": 1}, 2], "same": 0});
+        let ours = json!({"a": 2, "list": [{"x": 2}, 2], "same": 1, "new": "o"});
+        let theirs = json!({"a": 3, "list": [2], "same": 1, "new": "t"});
+        let merge = merge(&base, &ours, &theirs);
+
+        assert_eq!(
+            merge.value,
+            json!({"a": 2, "list": [{"x": 2}, 2], "same": 1, "new": "o"})
+        );
+        let conflict = |pointer: &str, ours: Option<Value>, theirs: Option<Value>| Conflict {
+            pointer: pointer.to_string(),
+            ours,
+            theirs,
+        };
+        assert_eq!(
+            merge.conflicts,
+            vec![
+                conflict("/a", Some(json!(2)), Some(json!(3))),
+                conflict("/list/0", Some(json!({"x": 2})), None),
+                conflict("/new", Some(json!("o")), Some(json!("t"))),
+            ]
+        );
+    }
+}
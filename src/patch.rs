@@ -0,0 +1,295 @@
+use serde_json::{json, Map, Value};
+
+use super::changes::pointer_token;
+use super::error::{Error, Result};
+use super::json_diff::{array_entries, ArrayDiff, ArrayEntry, JsonV, ObjectDiff};
+
+#[derive(Clone, Debug, Default)]
+pub struct PatchOptions {
+    /// Precede every `remove` and `replace` with a `test` of the value being removed, so
+    /// the patch fails on documents other than the first.
+    pub test: bool,
+}
+
+/// Generates an RFC 6902 JSON Patch transforming the first document into the second.
+pub fn json_patch(json: &JsonV) -> Value {
+    json_patch_with_options(json, &PatchOptions::default())
+}
+
+pub fn json_patch_with_options(json: &JsonV, options: &PatchOptions) -> Value {
+    let mut operations = Vec::new();
+    patch_rec(json, "", options, &mut operations);
+
+    Value::Array(operations)
+}
+
+fn patch_rec(json: &JsonV, pointer: &str, options: &PatchOptions, operations: &mut Vec<Value>) {
+    let remove = |pointer: &str, old: Value, operations: &mut Vec<Value>| {
+        if options.test {
+            operations.push(json!({"op": "test", "path": pointer, "value": old}));
+        }
+        operations.push(json!({"op": "remove", "path": pointer}));
+    };
+    match json {
+        JsonV::Null(Some(_))
+        | JsonV::Bool(_, Some(_))
+        | JsonV::Number(_, Some(_), _)
+        | JsonV::String(_, Some(_), _)
+        | JsonV::TypeChanged(_, _, _)
+        | JsonV::Replaced(_) => {
+            if options.test {
+                operations
+                    .push(json!({"op": "test", "path": pointer, "value": json.first_value()}));
+            }
+            operations
+                .push(json!({"op": "replace", "path": pointer, "value": json.second_value()}));
+        }
+        JsonV::Object(_, differences, _) => {
+            for d in differences {
+                match d {
+                    ObjectDiff::ObjectKeyPresent(k, v) => {
+                        let child = format!("{}/{}", pointer, pointer_token(k));
+                        remove(&child, v.first_value(), operations);
+                    }
+                    ObjectDiff::ObjectKeyMissing(k, v) => {
+                        let child = format!("{}/{}", pointer, pointer_token(k));
+                        operations
+                            .push(json!({"op": "add", "path": child, "value": v.second_value()}));
+                    }
+                    ObjectDiff::ObjectValueDiff(k, v) => {
+                        let child = format!("{}/{}", pointer, pointer_token(k));
+                        patch_rec(v, &child, options, operations);
+                    }
+                    ObjectDiff::ObjectKeyRenamed(old, new, v) => {
+                        let (from, child) = (
+                            format!("{}/{}", pointer, pointer_token(old)),
+                            format!("{}/{}", pointer, pointer_token(new)),
+                        );
+                        if options.test {
+                            operations.push(
+                                json!({"op": "test", "path": from, "value": v.first_value()}),
+                            );
+                        }
+                        operations.push(json!({"op": "move", "from": from, "path": child}));
+                        patch_rec(v, &child, options, operations);
+                    }
+                    // Key order is not significant in json objects
+                    ObjectDiff::ObjectKeysReordered(_, _) => (),
+                }
+            }
+        }
+        JsonV::Array(same, differences) => {
+            let entries = array_entries(same, differences);
+            // The array as patched so far, by the index of each element in the first array
+            // or None for added ones
+            let first_len = entries
+                .iter()
+                .filter(|x| match x {
+                    ArrayEntry::Same(_) => true,
+                    ArrayEntry::Diff(d) => !matches!(
+                        d,
+                        ArrayDiff::ArrayValueInSecond(_, _) | ArrayDiff::ArrayValueMoved(_, _, _)
+                    ),
+                })
+                .count();
+            let mut current: Vec<Option<usize>> = (0..first_len).map(Some).collect();
+            let find = |current: &[Option<usize>], i: usize| {
+                current.iter().position(|x| *x == Some(i)).unwrap_or(0)
+            };
+            // The index in the first array of the next element, and the index just after
+            // the elements already in their place in the second
+            let (mut left, mut index) = (0, 0);
+            for entry in entries {
+                match entry {
+                    ArrayEntry::Same(_) => {
+                        index = find(&current, left) + 1;
+                        left += 1;
+                    }
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueInFirst(_, v)) => {
+                        let position = find(&current, left);
+                        let child = format!("{}/{}", pointer, position);
+                        remove(&child, v.first_value(), operations);
+                        current.remove(position);
+                        left += 1;
+                    }
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueInSecond(_, v)) => {
+                        let child = format!("{}/{}", pointer, index);
+                        operations
+                            .push(json!({"op": "add", "path": child, "value": v.second_value()}));
+                        current.insert(index, None);
+                        index += 1;
+                    }
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueDiff(_, v)) => {
+                        let position = find(&current, left);
+                        patch_rec(v, &format!("{}/{}", pointer, position), options, operations);
+                        index = position + 1;
+                        left += 1;
+                    }
+                    // Moved when reaching the place it is moved to
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueMovedAway(_, _, _)) => left += 1,
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueMoved(_, from, v)) => {
+                        let position = find(&current, *from);
+                        current.remove(position);
+                        if position < index {
+                            index -= 1;
+                        }
+                        current.insert(index, Some(*from));
+                        let child = format!("{}/{}", pointer, index);
+                        operations.push(json!({
+                            "op": "move",
+                            "from": format!("{}/{}", pointer, position),
+                            "path": child,
+                        }));
+                        patch_rec(v, &child, options, operations);
+                        index += 1;
+                    }
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Generates an RFC 7386 JSON Merge Patch transforming the first document into the
+/// second. Changed arrays are replaced as a whole. Fails for changes a merge patch
+/// cannot express, which are values set to null or containing objects with null members.
+pub fn merge_patch(json: &JsonV) -> Result<Value> {
+    match json {
+        JsonV::Object(_, _, _) => merge_patch_rec(json, ""),
+        // A non-object patch replaces the whole document, nulls included, but an object
+        // is merged into an empty one and loses its null members
+        _ => {
+            let value = json.second_value();
+            if value.is_object() {
+                check_merge_value(&value, "")?;
+            }
+            Ok(value)
+        }
+    }
+}
+
+fn merge_patch_rec(json: &JsonV, pointer: &str) -> Result<Value> {
+    let mut patch = Map::new();
+    if let JsonV::Object(_, differences, _) = json {
+        for d in differences {
+            let (k, value) = match d {
+                ObjectDiff::ObjectKeyPresent(k, _) => (k, Value::Null),
+                ObjectDiff::ObjectKeyMissing(k, v) => (k, v.second_value()),
+                ObjectDiff::ObjectValueDiff(k, v @ JsonV::Object(_, _, _)) => {
+                    let child = format!("{}/{}", pointer, pointer_token(k));
+                    (k, merge_patch_rec(v, &child)?)
+                }
+                ObjectDiff::ObjectValueDiff(k, v) => (k, v.second_value()),
+                // A merge patch renames by deleting the old key and adding the new one
+                ObjectDiff::ObjectKeyRenamed(old, new, v) => {
+                    patch.insert(old.to_string(), Value::Null);
+                    (new, v.second_value())
+                }
+                ObjectDiff::ObjectKeysReordered(_, _) => continue,
+            };
+            let child = format!("{}/{}", pointer, pointer_token(k));
+            if !matches!(d, ObjectDiff::ObjectKeyPresent(_, _)) {
+                check_merge_value(&value, &child)?;
+            }
+            patch.insert(k.to_string(), value);
+        }
+    }
+
+    Ok(Value::Object(patch))
+}
+
+/// Merging deletes keys whose value is null, so nulls can only be set inside arrays.
+fn check_merge_value(value: &Value, pointer: &str) -> Result<()> {
+    match value {
+        Value::Null => Err(Error::Unrepresentable(
+            pointer.to_string(),
+            "a merge patch cannot set a value to null".to_string(),
+        )),
+        Value::Object(o) => o.iter().try_for_each(|(k, v)| {
+            check_merge_value(v, &format!("{}/{}", pointer, pointer_token(k)))
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::json_diff::diff;
+
+    #[test]
+    fn test_json_patch() {
+        let r = r#"{"a": 1, "list": [1, 2, 3, {"x": "y"}], "gone": 0}"#;
+        let r2 = r#"{"a": 2, "list": [0, 1, 3, {"x": "z"}], "new": [0]}"#;
+        let patch = json_patch(&diff(r, r2).unwrap());
+
+        let expected = json!([
+            {"op": "remove", "path": "/gone"},
+            {"op": "add", "path": "/new", "value": [0]},
+            {"op": "replace", "path": "/a", "value": 2},
+            {"op": "add", "path": "/list/0", "value": 0},
+            {"op": "remove", "path": "/list/2"},
+            {"op": "replace", "path": "/list/3/x", "value": "z"},
+        ]);
+        assert_eq!(patch, expected);
+    }
+
+    #[test]
+    fn test_json_patch_with_tests() {
+        let options = PatchOptions { test: true };
+        let patch = json_patch_with_options(&diff("[1, 2]", "[2]").unwrap(), &options);
+
+        let expected = json!([
+            {"op": "test", "path": "/0", "value": 1},
+            {"op": "remove", "path": "/0"},
+        ]);
+        assert_eq!(patch, expected);
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let r = r#"{"a": 1, "b": {"c": [1, 2], "d": "x", "e": {}}, "gone": 0, "same": 5}"#;
+        let r2 =
+            r#"{"a": 2, "b": {"c": [1], "d": "x", "e": {"f": true}}, "new": [null], "same": 5}"#;
+        let patch = merge_patch(&diff(r, r2).unwrap()).unwrap();
+
+        let expected = json!({
+            "gone": null,
+            "new": [null],
+            "a": 2,
+            "b": {"c": [1], "e": {"f": true}},
+        });
+        assert_eq!(patch, expected);
+        assert_eq!(
+            merge_patch(&diff("[1]", "[1]").unwrap()).unwrap(),
+            json!([1])
+        );
+        assert_eq!(
+            merge_patch(&diff("\"x\"", "\"x\"").unwrap()).unwrap(),
+            json!("x")
+        );
+    }
+
+    #[test]
+    fn test_merge_patch_null() {
+        let result = merge_patch(&diff(r#"{"a": 1}"#, r#"{"a": {"b": null}}"#).unwrap());
+
+        assert_eq!(
+            result,
+            Err(Error::Unrepresentable(
+                "/a/b".to_string(),
+                "a merge patch cannot set a value to null".to_string()
+            ))
+        );
+        let result = merge_patch(&diff("false", r#"{"y": 3, "w": null}"#).unwrap());
+        assert_eq!(
+            result,
+            Err(Error::Unrepresentable(
+                "/w".to_string(),
+                "a merge patch cannot set a value to null".to_string()
+            ))
+        );
+        assert_eq!(merge_patch(&diff("1", "null").unwrap()), Ok(Value::Null));
+    }
+}
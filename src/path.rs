@@ -0,0 +1,171 @@
+use super::error::{Error, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Any,
+    Recursive,
+}
+
+/// A pattern over document paths, used to scope diff options to parts of a document.
+///
+/// Patterns use either JSON Pointer syntax (`/items/*/etag`), where `*` matches any single
+/// key or array index and `**` matches any number of them, or JSONPath syntax
+/// (`$.items[*].etag`, `$..requestId`) with `..` for recursive descent.
+#[derive(Clone, Debug)]
+pub struct PathPattern {
+    segments: Vec<Segment>,
+}
+
+impl PathPattern {
+    /// A pattern matching every path in the document.
+    pub fn any() -> PathPattern {
+        PathPattern {
+            segments: vec![Segment::Recursive],
+        }
+    }
+
+    /// Parses a JSON Pointer pattern, which must be empty or start with `/`, or a JSONPath
+    /// pattern starting with `$`.
+    pub fn parse(pattern: &str) -> Result<PathPattern> {
+        let invalid = |reason: &str| Error::Pattern(pattern.to_string(), reason.to_string());
+        if let Some(json_path) = pattern.strip_prefix('$') {
+            return Ok(PathPattern {
+                segments: parse_json_path(json_path).map_err(invalid)?,
+            });
+        }
+        if !pattern.is_empty() && !pattern.starts_with('/') {
+            return Err(invalid(
+                "expected a JSON Pointer starting with `/` or a JSONPath starting with `$`",
+            ));
+        }
+        let segments = pattern
+            .split('/')
+            .skip(1)
+            .map(|token| match token {
+                "*" => Segment::Any,
+                "**" => Segment::Recursive,
+                _ => Segment::Key(token.replace("~1", "/").replace("~0", "~")),
+            })
+            .collect();
+
+        Ok(PathPattern { segments })
+    }
+
+    pub fn matches(&self, path: &[String]) -> bool {
+        matches_rec(&self.segments, path)
+    }
+}
+
+fn parse_json_path(pattern: &str) -> std::result::Result<Vec<Segment>, &'static str> {
+    let mut segments = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                if chars.peek() == Some(&'.') {
+                    chars.next();
+                    segments.push(Segment::Recursive);
+                }
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|x| *x != '.' && *x != '[') {
+                    name.push(c);
+                }
+                match name.as_str() {
+                    "" => (),
+                    "*" => segments.push(Segment::Any),
+                    _ => segments.push(Segment::Key(name)),
+                }
+            }
+            '[' => {
+                let mut selector = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(c) => selector.push(c),
+                        None => return Err("unclosed `[`"),
+                    }
+                }
+                let selector = selector.trim();
+                if selector == "*" {
+                    segments.push(Segment::Any);
+                } else {
+                    let quoted = selector
+                        .strip_prefix('\'')
+                        .and_then(|x| x.strip_suffix('\''))
+                        .or_else(|| selector.strip_prefix('"').and_then(|x| x.strip_suffix('"')));
+                    segments.push(Segment::Key(quoted.unwrap_or(selector).to_string()));
+                }
+            }
+            _ => return Err("expected `.` or `[`"),
+        }
+    }
+
+    Ok(segments)
+}
+
+fn matches_rec(segments: &[Segment], path: &[String]) -> bool {
+    match (segments.first(), path.first()) {
+        (None, None) => true,
+        (Some(Segment::Recursive), _) => {
+            matches_rec(&segments[1..], path)
+                || (!path.is_empty() && matches_rec(segments, &path[1..]))
+        }
+        (Some(Segment::Any), Some(_)) => matches_rec(&segments[1..], &path[1..]),
+        (Some(Segment::Key(k)), Some(p)) if k == p => matches_rec(&segments[1..], &path[1..]),
+        _ => false,
+    }
+}
+
+/// Returns the value of the first rule whose pattern matches `path`.
+pub fn find_rule<'a, T>(rules: &'a [(PathPattern, T)], path: &[String]) -> Option<&'a T> {
+    rules
+        .iter()
+        .find(|(pattern, _)| pattern.matches(path))
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn path(p: &[&str]) -> Vec<String> {
+        p.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pointer_patterns() -> Result<()> {
+        assert!(PathPattern::parse("")?.matches(&path(&[])));
+        assert!(PathPattern::parse("/items")?.matches(&path(&["items"])));
+        assert!(!PathPattern::parse("/items")?.matches(&path(&["items", "0"])));
+        assert!(PathPattern::parse("/items/*/etag")?.matches(&path(&["items", "3", "etag"])));
+        assert!(PathPattern::parse("/a~1b")?.matches(&path(&["a/b"])));
+        assert!(PathPattern::parse("/**/id")?.matches(&path(&["a", "b", "id"])));
+        assert!(PathPattern::parse("/**/id")?.matches(&path(&["id"])));
+        assert!(PathPattern::any().matches(&path(&["x", "y"])));
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_path_patterns() -> Result<()> {
+        assert!(PathPattern::parse("$")?.matches(&path(&[])));
+        assert!(PathPattern::parse("$.meta.timestamp")?.matches(&path(&["meta", "timestamp"])));
+        assert!(PathPattern::parse("$..requestId")?.matches(&path(&["a", "0", "requestId"])));
+        assert!(PathPattern::parse("$..requestId")?.matches(&path(&["requestId"])));
+        assert!(PathPattern::parse("$.items[*].etag")?.matches(&path(&["items", "1", "etag"])));
+        assert!(PathPattern::parse("$['a.b'][2]")?.matches(&path(&["a.b", "2"])));
+        assert!(!PathPattern::parse("$.items[*].etag")?.matches(&path(&["items", "etag"])));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        for pattern in ["items/etag", "*/etag", "$items", "$.items[0"] {
+            assert!(matches!(
+                PathPattern::parse(pattern),
+                Err(Error::Pattern(p, _)) if p == pattern
+            ));
+        }
+    }
+}
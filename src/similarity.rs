@@ -0,0 +1,136 @@
+use super::changes::pointer_token;
+use super::json_diff::{array_entries, has_differences, ArrayDiff, ArrayEntry, JsonV, ObjectDiff};
+
+/// How similar the two sides of a diff are, from 0.0 for nothing in common to 1.0 for
+/// equal.
+pub fn similarity(j: &JsonV) -> f64 {
+    match j {
+        JsonV::Object(similarities, differences, _) => {
+            let mut total = similarities.len();
+            let mut matched = similarities.len() as f64;
+            for d in differences {
+                match d {
+                    ObjectDiff::ObjectValueDiff(_, v) => {
+                        total += 1;
+                        matched += similarity(v);
+                    }
+                    ObjectDiff::ObjectKeyMissing(_, _) | ObjectDiff::ObjectKeyPresent(_, _) => {
+                        total += 1
+                    }
+                    // Both keys count as unmatched, their values as matched
+                    ObjectDiff::ObjectKeyRenamed(_, _, v) => {
+                        total += 2;
+                        matched += similarity(v);
+                    }
+                    ObjectDiff::ObjectKeysReordered(_, _) => (),
+                }
+            }
+            if total == 0 {
+                1.0
+            } else {
+                matched / total as f64
+            }
+        }
+        JsonV::Array(same, differences) => {
+            let (mut len1, mut len2) = (same.len(), same.len());
+            let mut matched = same.len() as f64;
+            for d in differences {
+                match d {
+                    ArrayDiff::ArrayValueDiff(_, v) => {
+                        len1 += 1;
+                        len2 += 1;
+                        matched += similarity(v);
+                    }
+                    ArrayDiff::ArrayValueInFirst(_, _) => len1 += 1,
+                    ArrayDiff::ArrayValueInSecond(_, _) => len2 += 1,
+                    ArrayDiff::ArrayValueMovedAway(_, _, _) => len1 += 1,
+                    ArrayDiff::ArrayValueMoved(_, _, v) => {
+                        len2 += 1;
+                        matched += similarity(v);
+                    }
+                }
+            }
+            if len1 + len2 == 0 {
+                1.0
+            } else {
+                2.0 * matched / (len1 + len2) as f64
+            }
+        }
+        _ if has_differences(j) => 0.0,
+        _ => 1.0,
+    }
+}
+
+/// The similarity of every value present in both documents, keyed by its JSON Pointer
+/// into the first document, in document order.
+pub fn subtree_similarities(json: &JsonV) -> Vec<(String, f64)> {
+    let mut output = Vec::new();
+    subtree_similarities_rec(json, "", &mut output);
+
+    output
+}
+
+fn subtree_similarities_rec(json: &JsonV, pointer: &str, output: &mut Vec<(String, f64)>) {
+    output.push((pointer.to_string(), similarity(json)));
+    match json {
+        JsonV::Object(similarities, differences, _) => {
+            let children = similarities
+                .iter()
+                .chain(differences.iter().filter_map(|d| match d {
+                    ObjectDiff::ObjectValueDiff(k, v) | ObjectDiff::ObjectKeyRenamed(k, _, v) => {
+                        Some((k, v))
+                    }
+                    _ => None,
+                }));
+            for (k, v) in children {
+                subtree_similarities_rec(v, &format!("{}/{}", pointer, pointer_token(k)), output);
+            }
+        }
+        JsonV::Array(same, differences) => {
+            let mut index = 0;
+            for entry in array_entries(same, differences) {
+                match entry {
+                    ArrayEntry::Same(v)
+                    | ArrayEntry::Diff(ArrayDiff::ArrayValueDiff(_, v))
+                    | ArrayEntry::Diff(ArrayDiff::ArrayValueMovedAway(_, _, v)) => {
+                        subtree_similarities_rec(v, &format!("{}/{}", pointer, index), output)
+                    }
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueInSecond(_, _))
+                    | ArrayEntry::Diff(ArrayDiff::ArrayValueMoved(_, _, _)) => continue,
+                    ArrayEntry::Diff(ArrayDiff::ArrayValueInFirst(_, _)) => (),
+                }
+                index += 1;
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::json_diff::diff;
+
+    #[test]
+    fn test_similarity() {
+        let r = r#"{"a": [1, 2, 3, 4], "b": {"c": 1, "d": 2}, "e": 1}"#;
+        let r2 = r#"{"a": [1, 3, 4, 5], "b": {"c": 1, "d": 3}, "f": 1}"#;
+        let json = diff(r, r2).unwrap();
+
+        assert_eq!(similarity(&diff(r, r).unwrap()), 1.0);
+        assert_eq!(
+            subtree_similarities(&json),
+            vec![
+                ("".to_string(), (0.75 + 0.5) / 4.0),
+                ("/a".to_string(), 0.75),
+                ("/a/0".to_string(), 1.0),
+                ("/a/2".to_string(), 1.0),
+                ("/a/3".to_string(), 1.0),
+                ("/b".to_string(), 0.5),
+                ("/b/c".to_string(), 1.0),
+                ("/b/d".to_string(), 0.0),
+            ]
+        );
+    }
+}
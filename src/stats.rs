@@ -0,0 +1,167 @@
+use super::json_diff::{ArrayDiff, JsonV, ObjectDiff};
+
+/// The number of changes of each kind.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Counts {
+    pub added_keys: usize,
+    pub removed_keys: usize,
+    /// Values of the same type that differ
+    pub changed: usize,
+    pub type_changes: usize,
+    pub array_insertions: usize,
+    pub array_deletions: usize,
+    /// Objects whose keys appear in a different order
+    pub reordered: usize,
+    /// Array elements moved to another index
+    pub moved: usize,
+    pub renamed_keys: usize,
+    /// The number of keys and indices leading to the deepest change, 0 at the root
+    pub max_depth: usize,
+}
+
+impl Counts {
+    /// The number of changes of all kinds.
+    pub fn total(&self) -> usize {
+        self.added_keys
+            + self.removed_keys
+            + self.changed
+            + self.type_changes
+            + self.array_insertions
+            + self.array_deletions
+            + self.reordered
+            + self.moved
+            + self.renamed_keys
+    }
+
+    fn record(&mut self, depth: usize) {
+        self.max_depth = self.max_depth.max(depth);
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Summary {
+    pub total: Counts,
+    /// The counts under each changed top-level key, when the documents are objects
+    pub sections: Vec<(String, Counts)>,
+}
+
+pub fn summary(json: &JsonV) -> Summary {
+    let mut total = Counts::default();
+    summary_rec(json, 0, &mut total);
+    let sections = match json {
+        JsonV::Object(_, differences, _) => differences
+            .iter()
+            .filter_map(|d| {
+                let k = match d {
+                    ObjectDiff::ObjectKeyMissing(k, _)
+                    | ObjectDiff::ObjectKeyPresent(k, _)
+                    | ObjectDiff::ObjectValueDiff(k, _)
+                    | ObjectDiff::ObjectKeyRenamed(_, k, _) => k,
+                    ObjectDiff::ObjectKeysReordered(_, _) => return None,
+                };
+                let mut counts = Counts::default();
+                object_diff_counts(d, 0, &mut counts);
+                Some((k.to_string(), counts))
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Summary { total, sections }
+}
+
+fn summary_rec(json: &JsonV, depth: usize, counts: &mut Counts) {
+    match json {
+        JsonV::Null(Some(_))
+        | JsonV::Bool(_, Some(_))
+        | JsonV::Number(_, Some(_), _)
+        | JsonV::String(_, Some(_), _)
+        | JsonV::Replaced(_) => {
+            counts.changed += 1;
+            counts.record(depth);
+        }
+        JsonV::TypeChanged(_, _, _) => {
+            counts.type_changes += 1;
+            counts.record(depth);
+        }
+        JsonV::Object(_, differences, _) => {
+            for d in differences {
+                object_diff_counts(d, depth, counts);
+            }
+        }
+        JsonV::Array(_, differences) => {
+            for d in differences {
+                match d {
+                    ArrayDiff::ArrayValueInSecond(_, _) => counts.array_insertions += 1,
+                    ArrayDiff::ArrayValueInFirst(_, _) => counts.array_deletions += 1,
+                    ArrayDiff::ArrayValueDiff(_, v) => summary_rec(v, depth + 1, counts),
+                    // Counted once, where the element is moved to
+                    ArrayDiff::ArrayValueMovedAway(_, _, _) => continue,
+                    ArrayDiff::ArrayValueMoved(_, _, v) => {
+                        counts.moved += 1;
+                        summary_rec(v, depth + 1, counts);
+                    }
+                }
+                counts.record(depth + 1);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Counts one difference of an object at the given depth.
+fn object_diff_counts(d: &ObjectDiff, depth: usize, counts: &mut Counts) {
+    match d {
+        ObjectDiff::ObjectKeyMissing(_, _) => counts.added_keys += 1,
+        ObjectDiff::ObjectKeyPresent(_, _) => counts.removed_keys += 1,
+        ObjectDiff::ObjectValueDiff(_, v) => summary_rec(v, depth + 1, counts),
+        ObjectDiff::ObjectKeyRenamed(_, _, v) => {
+            counts.renamed_keys += 1;
+            summary_rec(v, depth + 1, counts);
+        }
+        ObjectDiff::ObjectKeysReordered(_, _) => {
+            counts.reordered += 1;
+            counts.record(depth);
+            return;
+        }
+    }
+    counts.record(depth + 1);
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::json_diff::diff;
+
+    #[test]
+    fn test_summary() {
+        let r = r#"{"a": 1, "b": {"c": [1, 2, {"d": true}]}, "gone": 0, "same": 1}"#;
+        let r2 = r#"{"a": "1", "b": {"c": [2, 3, {"d": false}]}, "new": 0, "same": 1}"#;
+        let summary = summary(&diff(r, r2).unwrap());
+
+        let total = Counts {
+            added_keys: 1,
+            removed_keys: 1,
+            changed: 1,
+            type_changes: 1,
+            array_insertions: 1,
+            array_deletions: 1,
+            reordered: 0,
+            moved: 0,
+            renamed_keys: 0,
+            max_depth: 4,
+        };
+        assert_eq!(summary.total, total);
+        assert_eq!(summary.total.total(), 6);
+        let sections: Vec<(&str, usize, usize)> = summary
+            .sections
+            .iter()
+            .map(|(k, c)| (k.as_str(), c.total(), c.max_depth))
+            .collect();
+        assert_eq!(
+            sections,
+            vec![("gone", 1, 1), ("new", 1, 1), ("a", 1, 1), ("b", 3, 4)]
+        );
+    }
+}
@@ -0,0 +1,877 @@
+use indexmap::IndexMap;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufReader, Bytes, Read};
+
+use super::changes::{changes_rec, pointer_token, Change, ChangeKind};
+use super::error::{Error, ParseError, Result};
+use super::json_diff::{child_path, diff_rec, DiffOptions};
+use super::path::find_rule;
+
+/// Diffs two documents read incrementally, passing each change to `on_change` as soon as
+/// it is found.
+///
+/// Objects and arrays are walked in step, so only the values that cannot be paired right
+/// away are held in memory. When the keys of two objects differ, the shorter of the two
+/// values is taken as out of place and held until its key turns up in the other object
+/// or the object ends, and the walk goes on with the other value. Ignored values are
+/// skipped without being held. Arrays with an identity key are paired by that key
+/// regardless of position, other arrays by position. Unordered arrays and paths with a
+/// comparator are read whole and diffed with `diff_rec`, and so are values whose keys
+/// are out of order.
+///
+/// Changes are passed in the order they are found rather than in the order of
+/// `DiffOptions::key_order`, and records that changed order in arrays with an identity
+/// key are not reported as moved. Key reordering, moves and renames are not detected, and
+/// setting `report_key_reordering`, `move_threshold` or `rename_threshold` is an error.
+pub fn diff_streams<A: Read, B: Read>(
+    a: A,
+    b: B,
+    options: &DiffOptions,
+    on_change: impl FnMut(Change),
+) -> Result<()> {
+    let unsupported = [
+        ("report_key_reordering", options.report_key_reordering),
+        ("move_threshold", options.move_threshold.is_some()),
+        ("rename_threshold", options.rename_threshold.is_some()),
+    ];
+    if let Some((option, _)) = unsupported.iter().find(|(_, set)| *set) {
+        return Err(Error::Unsupported(option.to_string()));
+    }
+    let mut stream_diff = StreamDiff {
+        a: Parser::new(a, Error::LeftParse),
+        b: Parser::new(b, Error::RightParse),
+        options,
+        on_change,
+    };
+    let (start_a, start_b) = (stream_diff.a.start_value()?, stream_diff.b.start_value()?);
+    stream_diff.diff(start_a, start_b, &[], "", "")?;
+    stream_diff.a.end()?;
+    stream_diff.b.end()
+}
+
+struct StreamDiff<'a, A: Read, B: Read, F: FnMut(Change)> {
+    a: Parser<A>,
+    b: Parser<B>,
+    options: &'a DiffOptions,
+    on_change: F,
+}
+
+impl<A: Read, B: Read, F: FnMut(Change)> StreamDiff<'_, A, B, F> {
+    fn diff(
+        &mut self,
+        start_a: Start,
+        start_b: Start,
+        path: &[String],
+        left: &str,
+        right: &str,
+    ) -> Result<()> {
+        if self.options.is_ignored(path) {
+            self.a.skip(start_a)?;
+            return self.b.skip(start_b);
+        }
+        let whole =
+            self.options.is_unordered(path) || find_rule(&self.options.comparators, path).is_some();
+        match (start_a, start_b) {
+            (Start::Object, Start::Object) if !whole => self.objects(path, left, right),
+            (Start::Array, Start::Array) if !whole => {
+                match self.options.identity_key(path).map(|x| x.to_string()) {
+                    Some(key) => self.keyed_arrays(&key, path, left, right),
+                    None => self.arrays(path, left, right),
+                }
+            }
+            (start_a, start_b) => {
+                let (a, b) = (self.a.finish(start_a)?, self.b.finish(start_b)?);
+                self.emit_diff(&a, &b, path, left, right);
+                Ok(())
+            }
+        }
+    }
+
+    fn objects(&mut self, path: &[String], left: &str, right: &str) -> Result<()> {
+        // Values whose key has not turned up in the other object yet
+        let mut pending_a: IndexMap<String, Value> = IndexMap::new();
+        let mut pending_b: IndexMap<String, Value> = IndexMap::new();
+        // The keys whose value is read next, None at the end of the object
+        let (mut key_a, mut key_b) = (self.a.next_key(true)?, self.b.next_key(true)?);
+        loop {
+            match (key_a.take(), key_b.take()) {
+                (None, None) => break,
+                (Some(ka), Some(kb)) if ka == kb => {
+                    let (start_a, start_b) = (self.a.start_value()?, self.b.start_value()?);
+                    let token = pointer_token(&ka);
+                    self.diff(
+                        start_a,
+                        start_b,
+                        &child_path(path, &ka),
+                        &format!("{}/{}", left, token),
+                        &format!("{}/{}", right, token),
+                    )?;
+                    key_a = self.a.next_key(false)?;
+                    key_b = self.b.next_key(false)?;
+                }
+                // After the other object ended, or with the value held on the other side
+                (Some(ka), None) => {
+                    self.take_key_a(ka, &mut pending_a, &mut pending_b, path, left, right)?;
+                    key_a = self.a.next_key(false)?;
+                }
+                (None, Some(kb)) => {
+                    self.take_key_b(kb, &mut pending_a, &mut pending_b, path, left, right)?;
+                    key_b = self.b.next_key(false)?;
+                }
+                (Some(ka), Some(kb)) if pending_b.contains_key(&ka) => {
+                    self.take_key_a(ka, &mut pending_a, &mut pending_b, path, left, right)?;
+                    key_a = self.a.next_key(false)?;
+                    key_b = Some(kb);
+                }
+                (Some(ka), Some(kb)) if pending_a.contains_key(&kb) => {
+                    self.take_key_b(kb, &mut pending_a, &mut pending_b, path, left, right)?;
+                    key_a = Some(ka);
+                    key_b = self.b.next_key(false)?;
+                }
+                (Some(ka), Some(kb)) if self.options.is_ignored(&child_path(path, &ka)) => {
+                    let start = self.a.start_value()?;
+                    self.a.skip(start)?;
+                    key_a = self.a.next_key(false)?;
+                    key_b = Some(kb);
+                }
+                (Some(ka), Some(kb)) if self.options.is_ignored(&child_path(path, &kb)) => {
+                    let start = self.b.start_value()?;
+                    self.b.skip(start)?;
+                    key_a = Some(ka);
+                    key_b = self.b.next_key(false)?;
+                }
+                // Neither key has turned up on the other side yet, the shorter value is
+                // held so that the longer one can still be streamed
+                (Some(ka), Some(kb)) => match shorter_value(&mut self.a, &mut self.b)? {
+                    Shorter::First(a) => {
+                        pending_a.insert(ka, a);
+                        key_a = self.a.next_key(false)?;
+                        key_b = Some(kb);
+                    }
+                    Shorter::Second(b) => {
+                        pending_b.insert(kb, b);
+                        key_a = Some(ka);
+                        key_b = self.b.next_key(false)?;
+                    }
+                },
+            }
+        }
+        for (key, a) in pending_a {
+            let key_path = child_path(path, &key);
+            if !self.options.is_ignored(&key_path)
+                && !self.options.is_missing_equivalent(&a, &key_path)
+            {
+                self.emit_removed(a, format!("{}/{}", left, pointer_token(&key)));
+            }
+        }
+        for (key, b) in pending_b {
+            let key_path = child_path(path, &key);
+            if !self.options.is_ignored(&key_path)
+                && !self.options.is_missing_equivalent(&b, &key_path)
+            {
+                self.emit_added(b, format!("{}/{}", right, pointer_token(&key)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the value of `key` in the first object, and diffs it with the value of the
+    /// same key in the second if it was held, or holds it.
+    fn take_key_a(
+        &mut self,
+        key: String,
+        pending_a: &mut IndexMap<String, Value>,
+        pending_b: &mut IndexMap<String, Value>,
+        path: &[String],
+        left: &str,
+        right: &str,
+    ) -> Result<()> {
+        let start = self.a.start_value()?;
+        if self.options.is_ignored(&child_path(path, &key)) {
+            return self.a.skip(start);
+        }
+        let a = self.a.finish(start)?;
+        match pending_b.shift_remove(&key) {
+            Some(b) => self.emit_key_diff(&a, &b, &key, path, left, right),
+            None => {
+                pending_a.insert(key, a);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Like `take_key_a`, for the second object.
+    fn take_key_b(
+        &mut self,
+        key: String,
+        pending_a: &mut IndexMap<String, Value>,
+        pending_b: &mut IndexMap<String, Value>,
+        path: &[String],
+        left: &str,
+        right: &str,
+    ) -> Result<()> {
+        let start = self.b.start_value()?;
+        if self.options.is_ignored(&child_path(path, &key)) {
+            return self.b.skip(start);
+        }
+        let b = self.b.finish(start)?;
+        match pending_a.shift_remove(&key) {
+            Some(a) => self.emit_key_diff(&a, &b, &key, path, left, right),
+            None => {
+                pending_b.insert(key, b);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn arrays(&mut self, path: &[String], left: &str, right: &str) -> Result<()> {
+        let (mut done_a, mut done_b) = (false, false);
+        for index in 0.. {
+            let more_a = !done_a && self.a.next_element(index == 0)?;
+            let more_b = !done_b && self.b.next_element(index == 0)?;
+            done_a = !more_a;
+            done_b = !more_b;
+            let (left_index, right_index) = (
+                format!("{}/{}", left, index),
+                format!("{}/{}", right, index),
+            );
+            match (more_a, more_b) {
+                (true, true) => {
+                    let (start_a, start_b) = (self.a.start_value()?, self.b.start_value()?);
+                    let element_path = child_path(path, &index.to_string());
+                    self.diff(start_a, start_b, &element_path, &left_index, &right_index)?;
+                }
+                (true, false) => {
+                    let a = self.a.value()?;
+                    self.emit_removed(a, left_index);
+                }
+                (false, true) => {
+                    let b = self.b.value()?;
+                    self.emit_added(b, right_index);
+                }
+                (false, false) => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pairs the elements of arrays of objects on the identity key. Each element is read
+    /// whole.
+    fn keyed_arrays(&mut self, key: &str, path: &[String], left: &str, right: &str) -> Result<()> {
+        let identity = |v: &Value| match v {
+            Value::Object(o) if o.contains_key(key) => format!("#{}={}", key, o[key]),
+            _ => v.to_string(),
+        };
+        // Elements whose identity has not turned up in the other array yet, by identity
+        let mut pending_a: HashMap<String, VecDeque<(usize, Value)>> = HashMap::new();
+        let mut pending_b: HashMap<String, VecDeque<(usize, Value)>> = HashMap::new();
+        let (mut done_a, mut done_b) = (false, false);
+        for index in 0.. {
+            let more_a = !done_a && self.a.next_element(index == 0)?;
+            let more_b = !done_b && self.b.next_element(index == 0)?;
+            done_a = !more_a;
+            done_b = !more_b;
+            if !more_a && !more_b {
+                break;
+            }
+            if more_a {
+                let a = self.a.value()?;
+                match take_pending(&mut pending_b, &identity(&a)) {
+                    Some((index_b, b)) => {
+                        self.emit_element_diff(&a, &b, (index, index_b), path, left, right)
+                    }
+                    None => pending_a
+                        .entry(identity(&a))
+                        .or_default()
+                        .push_back((index, a)),
+                }
+            }
+            if more_b {
+                let b = self.b.value()?;
+                match take_pending(&mut pending_a, &identity(&b)) {
+                    Some((index_a, a)) => {
+                        self.emit_element_diff(&a, &b, (index_a, index), path, left, right)
+                    }
+                    None => pending_b
+                        .entry(identity(&b))
+                        .or_default()
+                        .push_back((index, b)),
+                }
+            }
+        }
+        for (index, a) in sorted_pending(pending_a) {
+            self.emit_removed(a, format!("{}/{}", left, index));
+        }
+        for (index, b) in sorted_pending(pending_b) {
+            self.emit_added(b, format!("{}/{}", right, index));
+        }
+
+        Ok(())
+    }
+
+    fn emit_diff(&mut self, a: &Value, b: &Value, path: &[String], left: &str, right: &str) {
+        let mut output = Vec::new();
+        changes_rec(
+            &diff_rec(a, b, self.options, path),
+            left,
+            right,
+            &mut output,
+        );
+        output.into_iter().for_each(&mut self.on_change);
+    }
+
+    fn emit_key_diff(
+        &mut self,
+        a: &Value,
+        b: &Value,
+        key: &str,
+        path: &[String],
+        left: &str,
+        right: &str,
+    ) {
+        let token = pointer_token(key);
+        self.emit_diff(
+            a,
+            b,
+            &child_path(path, key),
+            &format!("{}/{}", left, token),
+            &format!("{}/{}", right, token),
+        );
+    }
+
+    fn emit_element_diff(
+        &mut self,
+        a: &Value,
+        b: &Value,
+        (index_a, index_b): (usize, usize),
+        path: &[String],
+        left: &str,
+        right: &str,
+    ) {
+        self.emit_diff(
+            a,
+            b,
+            &child_path(path, &index_a.to_string()),
+            &format!("{}/{}", left, index_a),
+            &format!("{}/{}", right, index_b),
+        );
+    }
+
+    fn emit_removed(&mut self, a: Value, left: String) {
+        (self.on_change)(Change {
+            kind: ChangeKind::Removed,
+            left: Some(left),
+            right: None,
+            old: Some(a),
+            new: None,
+        });
+    }
+
+    fn emit_added(&mut self, b: Value, right: String) {
+        (self.on_change)(Change {
+            kind: ChangeKind::Added,
+            left: None,
+            right: Some(right),
+            old: None,
+            new: Some(b),
+        });
+    }
+}
+
+fn take_pending(
+    pending: &mut HashMap<String, VecDeque<(usize, Value)>>,
+    identity: &str,
+) -> Option<(usize, Value)> {
+    let elements = pending.get_mut(identity)?;
+    let element = elements.pop_front();
+    if elements.is_empty() {
+        pending.remove(identity);
+    }
+    element
+}
+
+fn sorted_pending(pending: HashMap<String, VecDeque<(usize, Value)>>) -> Vec<(usize, Value)> {
+    let mut elements: Vec<(usize, Value)> = pending.into_values().flatten().collect();
+    elements.sort_by_key(|(index, _)| *index);
+    elements
+}
+
+/// Reads the next value of both parsers side by side until one of them ends, which is
+/// returned. The other parser is rewound to the start of its value, so that holding a
+/// value costs at most twice the shorter one.
+fn shorter_value<A: Read, B: Read>(a: &mut Parser<A>, b: &mut Parser<B>) -> Result<Shorter> {
+    let (mut raw_a, mut raw_b) = (RawValue::start(a)?, RawValue::start(b)?);
+    loop {
+        if raw_a.step(a)? {
+            b.rewind(raw_b);
+            return Ok(Shorter::First(raw_a.value(a)?));
+        }
+        if raw_b.step(b)? {
+            a.rewind(raw_a);
+            return Ok(Shorter::Second(raw_b.value(b)?));
+        }
+    }
+}
+
+enum Shorter {
+    First(Value),
+    Second(Value),
+}
+
+/// The bytes of a value read so far, one at a time.
+struct RawValue {
+    bytes: Vec<u8>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    /// The position of the parser at the start of the value
+    position: Position,
+}
+
+impl RawValue {
+    fn start<R: Read>(parser: &mut Parser<R>) -> Result<RawValue> {
+        parser.skip_whitespace()?;
+        Ok(RawValue {
+            bytes: Vec::new(),
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            position: parser.position(),
+        })
+    }
+
+    /// Reads the next byte of the value, and returns whether the value is complete.
+    fn step<R: Read>(&mut self, parser: &mut Parser<R>) -> Result<bool> {
+        let byte = match parser.peek()? {
+            Some(byte) => byte,
+            None => return Err(parser.error("EOF while parsing a value")),
+        };
+        match self.bytes.first() {
+            None if !b"\"{[-0123456789tfn".contains(&byte) => {
+                parser.next_byte()?;
+                return Err(parser.error("expected value"));
+            }
+            // A number or a literal ends at the first byte that is not part of it
+            Some(first)
+                if !b"\"{[".contains(first)
+                    && !byte.is_ascii_alphanumeric()
+                    && !b"+-.".contains(&byte) =>
+            {
+                return Ok(true);
+            }
+            _ => (),
+        }
+        parser.next_byte()?;
+        self.bytes.push(byte);
+        if self.in_string {
+            match byte {
+                _ if self.escaped => self.escaped = false,
+                b'\\' => self.escaped = true,
+                b'"' => self.in_string = false,
+                _ => (),
+            }
+        } else {
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' => self.depth = self.depth.saturating_sub(1),
+                _ => (),
+            }
+        }
+        let scalar = b"-0123456789tfn".contains(&self.bytes[0]);
+
+        Ok(self.depth == 0 && !self.in_string && !scalar)
+    }
+
+    fn value<R: Read>(self, parser: &Parser<R>) -> Result<Value> {
+        serde_json::from_slice(&self.bytes).map_err(|e| parser.error(&invalid_string(&e)))
+    }
+}
+
+/// The line, column and last characters read by a parser.
+type Position = (usize, usize, VecDeque<u8>);
+
+/// The beginning of a value. Containers are read further by the caller, scalars are
+/// read whole.
+enum Start {
+    Object,
+    Array,
+    Scalar(Value),
+}
+
+/// A pull parser reading one value at a time.
+struct Parser<R: Read> {
+    input: Bytes<BufReader<R>>,
+    /// Bytes put back by `rewind`, read before the input
+    replay: VecDeque<u8>,
+    peeked: Option<u8>,
+    line: usize,
+    column: usize,
+    /// The last characters read, for the snippet of parse errors
+    recent: VecDeque<u8>,
+    /// Wraps parse errors as errors of the first or the second document
+    side: fn(ParseError) -> Error,
+}
+
+impl<R: Read> Parser<R> {
+    fn new(input: R, side: fn(ParseError) -> Error) -> Parser<R> {
+        Parser {
+            input: BufReader::new(input).bytes(),
+            replay: VecDeque::new(),
+            peeked: None,
+            line: 1,
+            column: 0,
+            recent: VecDeque::new(),
+            side,
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        (self.side)(ParseError {
+            message: format!("{} at line {} column {}", message, self.line, self.column),
+            line: self.line,
+            column: self.column,
+            snippet: String::from_utf8_lossy(&self.recent.iter().copied().collect::<Vec<u8>>())
+                .to_string(),
+        })
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        if self.peeked.is_none() {
+            self.peeked = match self.replay.pop_front() {
+                Some(byte) => Some(byte),
+                None => match self.input.next() {
+                    Some(byte) => Some(byte.map_err(|e| Error::Io(e.to_string()))?),
+                    None => None,
+                },
+            };
+        }
+
+        Ok(self.peeked)
+    }
+
+    fn next_byte(&mut self) -> Result<Option<u8>> {
+        let byte = self.peek()?;
+        self.peeked = None;
+        match byte {
+            Some(b'\n') => {
+                self.line += 1;
+                self.column = 0;
+                self.recent.clear();
+            }
+            Some(b) => {
+                self.column += 1;
+                if self.recent.len() == 20 {
+                    self.recent.pop_front();
+                }
+                self.recent.push_back(b);
+            }
+            None => (),
+        }
+
+        Ok(byte)
+    }
+
+    fn position(&self) -> Position {
+        (self.line, self.column, self.recent.clone())
+    }
+
+    /// Puts back the bytes of a value partly read, to be read again from its start.
+    fn rewind(&mut self, raw: RawValue) {
+        let mut replay: VecDeque<u8> = raw.bytes.into();
+        replay.extend(self.peeked.take());
+        replay.append(&mut self.replay);
+        self.replay = replay;
+        (self.line, self.column, self.recent) = raw.position;
+    }
+
+    /// The next byte that is not whitespace, without consuming it.
+    fn skip_whitespace(&mut self) -> Result<Option<u8>> {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek()? {
+            self.next_byte()?;
+        }
+        self.peek()
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<()> {
+        match self.skip_whitespace()? {
+            Some(byte) if byte == expected => {
+                self.next_byte()?;
+                Ok(())
+            }
+            Some(_) => {
+                self.next_byte()?;
+                Err(self.error(&format!("expected `{}`", expected as char)))
+            }
+            None => Err(self.error("EOF while parsing")),
+        }
+    }
+
+    fn start_value(&mut self) -> Result<Start> {
+        match self.skip_whitespace()? {
+            Some(b'{') => {
+                self.next_byte()?;
+                Ok(Start::Object)
+            }
+            Some(b'[') => {
+                self.next_byte()?;
+                Ok(Start::Array)
+            }
+            Some(b'"') => Ok(Start::Scalar(Value::String(self.string()?))),
+            Some(b'-' | b'0'..=b'9') => {
+                self.scalar(|b| b"+-.eE".contains(&b) || b.is_ascii_digit())
+            }
+            Some(b't' | b'f' | b'n') => self.scalar(|b| b.is_ascii_alphabetic()),
+            Some(_) => {
+                self.next_byte()?;
+                Err(self.error("expected value"))
+            }
+            None => Err(self.error("EOF while parsing a value")),
+        }
+    }
+
+    /// Reads a number or a literal made of the bytes accepted by `part`.
+    fn scalar(&mut self, part: fn(u8) -> bool) -> Result<Start> {
+        let mut text = Vec::new();
+        while let Some(byte) = self.peek()? {
+            if !part(byte) {
+                break;
+            }
+            text.push(byte);
+            self.next_byte()?;
+        }
+        serde_json::from_slice(&text)
+            .map(Start::Scalar)
+            .map_err(|_| self.error("invalid value"))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect(b'"')?;
+        let mut text = vec![b'"'];
+        loop {
+            match self.next_byte()? {
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    text.push(b'\\');
+                    match self.next_byte()? {
+                        Some(byte) => text.push(byte),
+                        None => return Err(self.error("EOF while parsing a string")),
+                    }
+                }
+                Some(byte) => text.push(byte),
+                None => return Err(self.error("EOF while parsing a string")),
+            }
+        }
+        text.push(b'"');
+        serde_json::from_slice(&text).map_err(|e| self.error(&invalid_string(&e)))
+    }
+
+    /// The next key of an object, or None at its end.
+    fn next_key(&mut self, first: bool) -> Result<Option<String>> {
+        if self.skip_whitespace()? == Some(b'}') {
+            self.next_byte()?;
+            return Ok(None);
+        }
+        if !first {
+            self.expect(b',')?;
+            self.skip_whitespace()?;
+        }
+        let key = self.string()?;
+        self.expect(b':')?;
+
+        Ok(Some(key))
+    }
+
+    /// Whether an array has another element.
+    fn next_element(&mut self, first: bool) -> Result<bool> {
+        if self.skip_whitespace()? == Some(b']') {
+            self.next_byte()?;
+            return Ok(false);
+        }
+        if !first {
+            self.expect(b',')?;
+        }
+
+        Ok(true)
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        let start = self.start_value()?;
+        self.finish(start)
+    }
+
+    /// Reads the rest of a value whose start has been read.
+    fn finish(&mut self, start: Start) -> Result<Value> {
+        match start {
+            Start::Object => {
+                let mut map = Map::new();
+                let mut first = true;
+                while let Some(key) = self.next_key(first)? {
+                    map.insert(key, self.value()?);
+                    first = false;
+                }
+                Ok(Value::Object(map))
+            }
+            Start::Array => {
+                let mut elements = Vec::new();
+                while self.next_element(elements.is_empty())? {
+                    elements.push(self.value()?);
+                }
+                Ok(Value::Array(elements))
+            }
+            Start::Scalar(value) => Ok(value),
+        }
+    }
+
+    /// Reads the rest of a value whose start has been read, without keeping it.
+    fn skip(&mut self, start: Start) -> Result<()> {
+        match start {
+            Start::Object => {
+                let mut first = true;
+                while self.next_key(first)?.is_some() {
+                    let start = self.start_value()?;
+                    self.skip(start)?;
+                    first = false;
+                }
+            }
+            Start::Array => {
+                let mut first = true;
+                while self.next_element(first)? {
+                    let start = self.start_value()?;
+                    self.skip(start)?;
+                    first = false;
+                }
+            }
+            Start::Scalar(_) => (),
+        }
+
+        Ok(())
+    }
+
+    /// Checks that nothing but whitespace follows the document.
+    fn end(&mut self) -> Result<()> {
+        match self.skip_whitespace()? {
+            Some(_) => {
+                self.next_byte()?;
+                Err(self.error("trailing characters"))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// The message of serde_json without its position, which is relative to the string.
+fn invalid_string(error: &serde_json::Error) -> String {
+    let message = error.to_string();
+    match message.find(" at line ") {
+        Some(end) => message[..end].to_string(),
+        None => message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::changes::changes;
+    use crate::json_diff::diff;
+    use crate::path::PathPattern;
+
+    fn stream_changes(r: &str, r2: &str, options: &DiffOptions) -> Result<Vec<Change>> {
+        let mut output = Vec::new();
+        diff_streams(r.as_bytes(), r2.as_bytes(), options, |c| output.push(c))?;
+        Ok(output)
+    }
+
+    #[test]
+    fn test_diff_streams() -> Result<()> {
+        let r = r#"{"a": 1, "b": {"c": [1, 2, {"d": "x\n"}], "e": null}, "gone": [1], "f": true}"#;
+        let r2 =
+            r#"{"a": 2, "b": {"c": [1, 2, {"d": "y"}, 4], "e": {}}, "new": {"x": 1}, "f": true}"#;
+        let streamed = stream_changes(r, r2, &DiffOptions::default())?;
+        let expected = changes(&diff(r, r2)?);
+
+        assert_eq!(streamed.len(), expected.len());
+        assert!(expected.iter().all(|c| streamed.contains(c)));
+
+        let options = DiffOptions {
+            identity_keys: vec![(PathPattern::parse("/items")?, "id".to_string())],
+            ..Default::default()
+        };
+        let r = r#"{"items": [{"id": 1, "v": 1}, {"id": 2, "v": 2}, {"id": 3}], "z": 0, "y": 1}"#;
+        let r2 = r#"{"items": [{"id": 2, "v": 3}, {"id": 1, "v": 1}, {"id": 4}], "y": 1, "z": 0}"#;
+        let pointers: Vec<(ChangeKind, Option<String>, Option<String>)> =
+            stream_changes(r, r2, &options)?
+                .into_iter()
+                .map(|c| (c.kind, c.left, c.right))
+                .collect();
+        assert_eq!(
+            pointers,
+            vec![
+                (
+                    ChangeKind::Changed,
+                    Some("/items/1/v".to_string()),
+                    Some("/items/0/v".to_string())
+                ),
+                (ChangeKind::Removed, Some("/items/2".to_string()), None),
+                (ChangeKind::Added, None, Some("/items/2".to_string())),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_out_of_place_keys() -> Result<()> {
+        // The extra key is held and the array is streamed, so its change is found
+        // before the first document turns out to be cut short
+        for (r, r2) in [
+            (r#"{"x": 0, "users": [1, 9, "#, r#"{"users": [1, 2, 3]}"#),
+            (
+                r#"{"users": [1, 9, 3, 4, "#,
+                r#"{"x": {"y": 0}, "users": [1, 2, 3, 4, 5]}"#,
+            ),
+        ] {
+            let mut output = Vec::new();
+            let result = diff_streams(r.as_bytes(), r2.as_bytes(), &DiffOptions::default(), |c| {
+                output.push(c)
+            });
+            assert!(matches!(result, Err(Error::LeftParse(_))));
+            assert_eq!(output[0].left.as_deref(), Some("/users/1"));
+        }
+
+        let r = r#"{"a": 1, "x": "long", "b": {"c": 2, "d": 0}, "z": 3}"#;
+        let r2 = r#"{"y": [0], "a": 1, "b": {"d": 0, "c": 3}, "z": 4, "x": "long"}"#;
+        let streamed = stream_changes(r, r2, &DiffOptions::default())?;
+        let expected = changes(&diff(r, r2)?);
+        assert_eq!(streamed.len(), expected.len());
+        assert!(expected.iter().all(|c| streamed.contains(c)));
+
+        let options = DiffOptions {
+            move_threshold: Some(1.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            stream_changes(r, r2, &options),
+            Err(Error::Unsupported("move_threshold".to_string()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_parse_error() {
+        let r = "{\"a\": [1,\n 2 3]}";
+
+        match stream_changes(r, r, &DiffOptions::default()) {
+            Err(Error::LeftParse(e)) => {
+                assert_eq!((e.line, e.column), (2, 4));
+                assert_eq!(e.snippet, " 2 3");
+            }
+            result => panic!("expected a parse error, got {:?}", result),
+        }
+    }
+}
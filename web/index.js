@@ -1,4 +1,4 @@
-import init, { find_diff } from "./jsondiff.js";
+import init, { find_diff, diff_summary } from "./jsondiff.js";
 
 
 let r = `
@@ -35,9 +35,18 @@ init()
             let r1 = document.getElementById("text1").value;
             let r2 = document.getElementById("text2").value;
             if (r1.length > 0 && r2.length > 0) {
-                let output = find_diff(r1, r2);
-                console.log("output", output);
-                document.getElementById("jsonresult").innerHTML = output;
+                try {
+                    let output = find_diff(r1, r2);
+                    console.log("output", output);
+                    document.getElementById("jsonresult").innerHTML = output;
+                    let summary = diff_summary(r1, r2);
+                    document.getElementById("summary").textContent =
+                        `${summary.total} changes in ${summary.sections.length} sections`;
+                } catch (error) {
+                    console.log("error", error.kind, error);
+                    document.getElementById("summary").textContent = "";
+                    document.getElementById("jsonresult").textContent = error.message;
+                }
             }
         });
     });
\ No newline at end of file